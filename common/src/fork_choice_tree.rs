@@ -1,7 +1,12 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{self, Write},
+};
 
 use commonware_cryptography::sha256::Digest;
+use commonware_runtime::Metrics;
 
+use prometheus_client::metrics::counter::Counter;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -12,25 +17,95 @@ pub enum ForkChoiceTreeError {
     InvalidBlockHeight(u64),
     #[error("failed to solve fork")]
     UnsolvableFork(Digest),
+    #[error("block too far from finalized head")]
+    ForkTooDeep(u64),
+}
+
+/// The outcome of finalizing a frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finalization {
+    pub frame_number: u64,
+    pub previous_head: Digest,
+    pub new_head: Digest,
+    /// Blocks finalized by the frame, from the oldest to the new head.
+    pub blocks: Vec<Digest>,
+    /// Whether a fork had to be resolved (discarding at least one competing subtree).
+    pub reorg: bool,
+}
+
+/// Factor (`numerator / denominator`) applied to all scores after every finalization
+/// attempt, so older proposals weigh less than recent ones (`numerator` must not exceed
+/// the non-zero `denominator`).
+#[derive(Clone, Copy, Debug)]
+pub struct ScoreDecay {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+pub struct ForkChoiceTreeConfig {
+    pub genesis_block_hash: Digest,
+    /// Parent of the genesis block, marking the root of the tree.
+    pub genesis_parent: Digest,
+    /// Maximum number of blocks a proposal may be ahead of the finalized head.
+    pub max_fork_depth: Option<u64>,
+    /// Decay of block scores (scores never decay if unset).
+    pub score_decay: Option<ScoreDecay>,
+    /// Score margin by which a competing subtree must outweigh the one chosen by the last
+    /// finalization attempt to be finalized instead.
+    pub hysteresis: u64,
 }
 
 pub struct ForkChoiceTree {
     nodes: HashMap<Digest, ForkChoiceTreeNode>,
+    genesis_parent: Digest,
 
     finalized_frame: u64,
     finalized_head: Digest,
+    finalized_blocks: Vec<Digest>,
+    /// Head finalized by each frame (the genesis block for the first one).
+    finalized_heads: BTreeMap<u64, Digest>,
+
+    max_fork_depth: Option<u64>,
+    score_decay: Option<ScoreDecay>,
+    hysteresis: u64,
+
+    created_blocks: Counter,
+    repeated_proposals: Counter,
 }
 
 impl ForkChoiceTree {
-    pub fn new(genesis_block_hash: Digest) -> Self {
+    pub fn new(context: impl Metrics, config: ForkChoiceTreeConfig) -> Self {
+        // Check config
+        if let Some(decay) = config.score_decay {
+            assert!(decay.denominator > 0, "score decay denominator must be positive");
+            assert!(decay.numerator <= decay.denominator, "score decay must not increase scores");
+        }
+
+        // Initialize metrics
+        let created_blocks = Counter::default();
+        let repeated_proposals = Counter::default();
+        context.register(
+            "created_blocks",
+            "Number of proposals that added a new block to the tree",
+            created_blocks.clone(),
+        );
+        context.register(
+            "repeated_proposals",
+            "Number of proposals that only increased the score of an existing block",
+            repeated_proposals.clone(),
+        );
+
+        let genesis_block_hash = config.genesis_block_hash;
         let root = ForkChoiceTreeNode {
             block_frame: 0,
             block_height: 0,
-            block_parent: [0; 32].into(),
+            block_parent: config.genesis_parent,
             block_hash: genesis_block_hash,
 
             score: 0,
+            proposals: 0,
             children: Vec::new(),
+            chosen_child: None,
         };
 
         let mut nodes = HashMap::<Digest, ForkChoiceTreeNode>::new();
@@ -38,24 +113,37 @@ impl ForkChoiceTree {
 
         Self {
             nodes,
+            genesis_parent: config.genesis_parent,
 
             finalized_frame: 1,
             finalized_head: genesis_block_hash,
+            finalized_blocks: Vec::new(),
+            finalized_heads: BTreeMap::from([(1, genesis_block_hash)]),
+
+            max_fork_depth: config.max_fork_depth,
+            score_decay: config.score_decay,
+            hysteresis: config.hysteresis,
+
+            created_blocks,
+            repeated_proposals,
         }
     }
     
-    pub fn propose_block(&mut self, height: u64, parent: Digest, hash: Digest) -> Result<(), ForkChoiceTreeError> {
+    pub fn propose_block(&mut self, height: u64, parent: Digest, hash: Digest, weight: u64) -> Result<(), ForkChoiceTreeError> {
         if !self.nodes.contains_key(&hash) {
-            self.create_node(height, parent, hash)
+            self.create_node(height, parent, hash, weight)?;
+            self.created_blocks.inc();
         } else {
-            self.increment_node_score(hash);
-            Ok(())
+            self.node_mut(hash).proposals += 1;
+            self.increment_node_score(hash, weight);
+            self.repeated_proposals.inc();
         }
+        Ok(())
     }
 
-    fn create_node(&mut self, block_height: u64, block_parent: Digest, block_hash: Digest) -> Result<(), ForkChoiceTreeError> {
+    fn create_node(&mut self, block_height: u64, block_parent: Digest, block_hash: Digest, weight: u64) -> Result<(), ForkChoiceTreeError> {
         // Check parent
-        let parent = if let Some(parent) = self.nodes.get_mut(&block_parent) {
+        let parent = if let Some(parent) = self.nodes.get(&block_parent) {
             parent
         } else {
             return Err(ForkChoiceTreeError::InvalidBlockParentHash(block_parent))
@@ -65,9 +153,17 @@ impl ForkChoiceTree {
         if block_height != parent.block_height + 1 {
             return Err(ForkChoiceTreeError::InvalidBlockHeight(block_height))
         };
+
+        // Check distance from finalized head
+        if let Some(max_fork_depth) = self.max_fork_depth {
+            let finalized_height = self.node(self.finalized_head).block_height;
+            if block_height.saturating_sub(finalized_height) > max_fork_depth {
+                return Err(ForkChoiceTreeError::ForkTooDeep(block_height))
+            }
+        }
         
         // Add node to tree
-        parent.children.push(block_hash);
+        self.node_mut(block_parent).children.push(block_hash);
         let node = ForkChoiceTreeNode{
             block_frame: self.finalized_frame + 1,
             block_height: block_height,
@@ -75,38 +171,75 @@ impl ForkChoiceTree {
             block_hash: block_hash,
 
             score: 0,
+            proposals: 1,
             children: Vec::new(),
+            chosen_child: None,
         };
         self.nodes.insert(block_hash, node);
-        self.increment_node_score(block_hash);
+        self.increment_node_score(block_hash, weight);
 
         Ok(())
     }
 
-    fn increment_node_score(&mut self, block_hash: Digest) {
+    fn increment_node_score(&mut self, block_hash: Digest, weight: u64) {
         let finalized_frame = self.finalized_frame;
+        let finalized_head = self.finalized_head;
+        let genesis_parent = self.genesis_parent;
 
-        // Increment parent score until finalized frame is reached
+        // Increment parent score until finalized frame (or the root) is reached
         let mut current_block_hash = block_hash;
         loop {
-            let node = self.node_mut(current_block_hash);
-            if node.block_frame == finalized_frame {
+            // Stop at the oldest retained block if older ones were pruned
+            let Some(node) = self.nodes.get_mut(&current_block_hash) else {
+                break;
+            };
+            if node.block_frame == finalized_frame || node.block_hash == finalized_head {
+                break;
+            }
+            node.score = node.score.saturating_add(weight);
+            if node.block_parent == genesis_parent {
                 break;
             }
-            node.score = node.score + 1;
             current_block_hash = node.block_parent;
         }
     }
 
-    pub fn finalize_block_frame(&mut self) -> Result<(u64, Digest), ForkChoiceTreeError> {
+    pub fn finalize_block_frame(&mut self) -> Result<Finalization, ForkChoiceTreeError> {
+        let result = self.solve_forks();
+        self.decay_scores();
+        result
+    }
+
+    fn decay_scores(&mut self) {
+        let Some(decay) = self.score_decay else {
+            return;
+        };
+        for node in self.nodes.values_mut() {
+            node.score = (node.score as u128 * decay.numerator as u128
+                / decay.denominator as u128) as u64;
+        }
+    }
+
+    fn solve_forks(&mut self) -> Result<Finalization, ForkChoiceTreeError> {
         let mut current_block_hash = self.finalized_head;
+        let mut reorg = false;
         loop {
             // All forks are solved and leaf node is reached
             let node = &self.node(current_block_hash);
             if node.is_leaf() {
+                self.finalized_blocks = self.path_between(self.finalized_head, current_block_hash)
+                    .expect("finalized head is not an ancestor of the new head");
+                let previous_head = self.finalized_head;
                 self.finalized_frame += 1;
                 self.finalized_head = current_block_hash;
-                return Ok((self.finalized_frame, self.finalized_head));
+                self.finalized_heads.insert(self.finalized_frame, current_block_hash);
+                return Ok(Finalization {
+                    frame_number: self.finalized_frame,
+                    previous_head,
+                    new_head: self.finalized_head,
+                    blocks: self.finalized_blocks.clone(),
+                    reorg,
+                });
             }
 
             // No fork at current node
@@ -127,16 +260,193 @@ impl ForkChoiceTree {
                     score_a.partial_cmp(&score_b).expect("failed to compare subtree scores")
                 })
                 .expect("tyring to solve fork for leaf node");
-            
-            // Check if fork is solvable (no other subtree doesn't have the same score as heaviest subtree)
-            if children.iter()
-                .filter(|child| child.score == heaviest_subtree_rrot.score)
-                .count() > 1 {
-                return Err(ForkChoiceTreeError::UnsolvableFork(current_block_hash))
+
+            // Keep the subtree chosen by the last attempt unless a competitor is clearly heavier
+            let incumbent = node.chosen_child
+                .filter(|_| self.hysteresis > 0)
+                .and_then(|block_hash| children.iter().find(|child| child.block_hash == block_hash));
+            let chosen_block_hash = match incumbent {
+                Some(incumbent)
+                    if heaviest_subtree_rrot.score <= incumbent.score.saturating_add(self.hysteresis) =>
+                {
+                    incumbent.block_hash
+                }
+                _ => {
+                    // Check if fork is solvable (no other subtree doesn't have the same score as heaviest subtree)
+                    if children.iter()
+                        .filter(|child| child.score == heaviest_subtree_rrot.score)
+                        .count() > 1 {
+                        return Err(ForkChoiceTreeError::UnsolvableFork(current_block_hash))
+                    }
+                    heaviest_subtree_rrot.block_hash
+                }
+            };
+
+            self.node_mut(current_block_hash).chosen_child = Some(chosen_block_hash);
+            current_block_hash = chosen_block_hash;
+            reorg = true;
+        }
+    }
+
+    pub fn finalized_frame(&self) -> u64 {
+        self.finalized_frame
+    }
+
+    pub fn finalized_head(&self) -> Digest {
+        self.finalized_head
+    }
+
+    /// Remove all blocks that can no longer be finalized (those not descending from the
+    /// finalized head), except the ancestors of the finalized head created since the given frame.
+    pub fn prune_below_frame(&mut self, frame: u64) {
+        // Keep the recent ancestors of the finalized head
+        let mut retained = HashSet::new();
+        let mut current = self.nodes.get(&self.finalized_head);
+        while let Some(node) = current {
+            if node.block_hash != self.finalized_head && node.block_frame < frame {
+                break;
             }
+            retained.insert(node.block_hash);
+            current = self.nodes.get(&node.block_parent);
+        }
+
+        // Keep all descendants of the finalized head
+        let mut pending = self.node(self.finalized_head).children.clone();
+        while let Some(block_hash) = pending.pop() {
+            if let Some(node) = self.nodes.get(&block_hash) {
+                retained.insert(block_hash);
+                pending.extend(node.children.iter().copied());
+            }
+        }
+
+        // Remove the other blocks (and any reference to them)
+        self.nodes.retain(|block_hash, _| retained.contains(block_hash));
+        for node in self.nodes.values_mut() {
+            node.children.retain(|child| retained.contains(child));
+        }
+        self.finalized_heads.retain(|_, head| retained.contains(head));
+    }
 
-            current_block_hash = heaviest_subtree_rrot.block_hash;
+    /// Check if the block is the finalized head or one of its ancestors.
+    pub fn is_finalized(&self, block_hash: Digest) -> bool {
+        let Some(height) = self.height(block_hash) else {
+            return false;
+        };
+        let mut current = self.node(self.finalized_head);
+        while current.block_height > height {
+            let Some(parent) = self.nodes.get(&current.block_parent) else {
+                return false;
+            };
+            current = parent;
         }
+        current.block_hash == block_hash
+    }
+
+    /// Blocks finalized by the last frame, from the oldest to the finalized head.
+    pub fn finalized_blocks(&self) -> &[Digest] {
+        &self.finalized_blocks
+    }
+
+    /// Iterate over the frames finalized since the given frame (included), from the oldest to
+    /// the latest, as `(frame_number, head, blocks)`. Frames whose blocks were pruned are skipped.
+    pub fn finalized_chain(&self, since: u64) -> impl Iterator<Item = (u64, Digest, Vec<Digest>)> + '_ {
+        self.finalized_heads
+            .range(since..)
+            .filter_map(move |(frame, head)| {
+                // The first frame has no previous head (and finalizes no blocks)
+                let previous = self.finalized_heads.get(&frame.checked_sub(1)?)?;
+                let blocks = self.path_between(*previous, *head)?;
+                Some((*frame, *head, blocks))
+            })
+    }
+
+    /// Get the path from `from` (excluded) to `to` (included), if `from` is an ancestor of `to`.
+    pub fn path_between(&self, from: Digest, to: Digest) -> Option<Vec<Digest>> {
+        let from_height = self.nodes.get(&from)?.block_height;
+        let mut path = Vec::new();
+        let mut current = self.nodes.get(&to)?;
+        while current.block_height > from_height {
+            path.push(current.block_hash);
+            current = self.nodes.get(&current.block_parent)?;
+        }
+        if current.block_hash != from {
+            return None;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Get all tips of the tree (blocks without children).
+    pub fn leaves(&self) -> Vec<Digest> {
+        self.nodes.values()
+            .filter(|node| node.is_leaf())
+            .map(|node| node.block_hash)
+            .collect()
+    }
+
+    /// Number of competing tips (leaves) beyond the first one.
+    pub fn fork_count(&self) -> usize {
+        self.leaves().len().saturating_sub(1)
+    }
+
+    pub fn depth_from_finalized(&self, block_hash: Digest) -> Option<u64> {
+        let node = self.nodes.get(&block_hash)?;
+        node.block_height.checked_sub(self.node(self.finalized_head).block_height)
+    }
+
+    pub fn score(&self, block_hash: Digest) -> Option<u64> {
+        self.nodes.get(&block_hash).map(|node| node.score)
+    }
+
+    pub fn proposal_count(&self, block_hash: Digest) -> Option<u64> {
+        self.nodes.get(&block_hash).map(|node| node.proposals)
+    }
+
+    /// Count the proposals of the block and all its descendants, recomputed from the tree
+    /// (matches the score when every proposal has a weight of 1 and scores don't decay).
+    pub fn subtree_weight(&self, block_hash: Digest) -> Option<u64> {
+        let mut weight = 0u64;
+        let mut pending = vec![self.nodes.get(&block_hash)?];
+        while let Some(node) = pending.pop() {
+            weight = weight.saturating_add(node.proposals);
+            pending.extend(node.children.iter().filter_map(|child| self.nodes.get(child)));
+        }
+        Some(weight)
+    }
+
+    pub fn height(&self, block_hash: Digest) -> Option<u64> {
+        self.nodes.get(&block_hash).map(|node| node.block_height)
+    }
+
+    pub fn children(&self, block_hash: Digest) -> Option<&[Digest]> {
+        self.nodes.get(&block_hash).map(|node| node.children.as_slice())
+    }
+
+    /// Render the tree in Graphviz DOT format (the finalized head is highlighted).
+    pub fn to_dot(&self) -> String {
+        let mut nodes = self.nodes.values().collect::<Vec<_>>();
+        nodes.sort_by_key(|node| (node.block_height, node.block_hash));
+
+        let mut dot = String::from("digraph ForkChoiceTree {\n");
+        for node in &nodes {
+            let style = if node.block_hash == self.finalized_head {
+                ", style=filled, fillcolor=lightblue"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                dot,
+                "    \"{}\" [label=\"height={} score={}\"{}];",
+                node.block_hash, node.block_height, node.score, style,
+            );
+        }
+        for node in &nodes {
+            for child in &node.children {
+                let _ = writeln!(dot, "    \"{}\" -> \"{}\";", node.block_hash, child);
+            }
+        }
+        dot.push_str("}\n");
+        dot
     }
 
     fn node(&self, block_hash: Digest) -> &ForkChoiceTreeNode {
@@ -148,6 +458,12 @@ impl ForkChoiceTree {
     }
 }
 
+impl fmt::Display for ForkChoiceTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_dot())
+    }
+}
+
 struct ForkChoiceTreeNode {
     pub block_frame: u64,
     pub block_height: u64,
@@ -155,11 +471,395 @@ struct ForkChoiceTreeNode {
     pub block_hash: Digest,
     
     pub score: u64,
+    pub proposals: u64,
     pub children: Vec<Digest>,
+    /// Child followed by the last finalization attempt through this block (if it forked).
+    pub chosen_child: Option<Digest>,
 }
 
 impl ForkChoiceTreeNode {
     pub fn is_leaf(&self) -> bool {
         self.children.is_empty()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commonware_runtime::{deterministic, Runner};
+
+    fn hash(n: u8) -> Digest {
+        [n; 32].into()
+    }
+
+    fn config() -> ForkChoiceTreeConfig {
+        ForkChoiceTreeConfig {
+            genesis_block_hash: hash(1),
+            genesis_parent: hash(0),
+            max_fork_depth: None,
+            score_decay: None,
+            hysteresis: 0,
+        }
+    }
+
+    #[test]
+    fn test_finalized_frame() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut tree = ForkChoiceTree::new(context, config());
+            assert_eq!(tree.finalized_frame(), 1);
+            assert_eq!(tree.finalized_head(), hash(1));
+
+            // Finalize a chain of two blocks
+            tree.propose_block(1, hash(1), hash(2), 1).unwrap();
+            tree.propose_block(2, hash(2), hash(3), 1).unwrap();
+            tree.finalize_block_frame().unwrap();
+            assert_eq!(tree.finalized_frame(), 2);
+            assert_eq!(tree.finalized_head(), hash(3));
+        });
+    }
+
+    #[test]
+    fn test_scores() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut tree = ForkChoiceTree::new(context, config());
+            tree.propose_block(1, hash(1), hash(2), 1).unwrap();
+            tree.propose_block(2, hash(2), hash(3), 1).unwrap();
+            tree.propose_block(2, hash(2), hash(4), 1).unwrap();
+            tree.propose_block(2, hash(2), hash(3), 1).unwrap();
+
+            // Every proposal increments the block and its ancestors (up to the finalized head)
+            assert_eq!(tree.score(hash(1)), Some(0));
+            assert_eq!(tree.score(hash(2)), Some(4));
+            assert_eq!(tree.score(hash(3)), Some(2));
+            assert_eq!(tree.score(hash(4)), Some(1));
+            assert_eq!(tree.score(hash(5)), None);
+            assert_eq!(tree.height(hash(3)), Some(2));
+            assert_eq!(tree.height(hash(5)), None);
+        });
+    }
+
+    #[test]
+    fn test_max_fork_depth() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut tree = ForkChoiceTree::new(context, ForkChoiceTreeConfig {
+                max_fork_depth: Some(2),
+                ..config()
+            });
+            tree.propose_block(1, hash(1), hash(2), 1).unwrap();
+            tree.propose_block(2, hash(2), hash(3), 1).unwrap();
+            assert_eq!(tree.depth_from_finalized(hash(3)), Some(2));
+
+            // Reject blocks too far ahead of the finalized head
+            assert!(matches!(
+                tree.propose_block(3, hash(3), hash(4), 1),
+                Err(ForkChoiceTreeError::ForkTooDeep(3))
+            ));
+            assert_eq!(tree.depth_from_finalized(hash(4)), None);
+
+            // The limit moves with the finalized head
+            tree.finalize_block_frame().unwrap();
+            tree.propose_block(3, hash(3), hash(4), 1).unwrap();
+            assert_eq!(tree.depth_from_finalized(hash(4)), Some(1));
+        });
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut tree = ForkChoiceTree::new(context, config());
+            tree.propose_block(1, hash(1), hash(2), 1).unwrap();
+            tree.propose_block(2, hash(2), hash(3), 1).unwrap();
+            tree.propose_block(2, hash(2), hash(4), 1).unwrap();
+
+            let dot = tree.to_dot();
+            assert!(dot.starts_with("digraph ForkChoiceTree {\n"));
+            assert_eq!(dot.matches("[label=").count(), 4);
+            assert_eq!(dot.matches(" -> ").count(), 3);
+            assert_eq!(dot.matches("fillcolor=lightblue").count(), 1);
+            assert_eq!(tree.to_string(), dot);
+        });
+    }
+
+    #[test]
+    fn test_repeated_proposals() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut tree = ForkChoiceTree::new(context, config());
+            tree.propose_block(1, hash(1), hash(2), 1).unwrap();
+            assert_eq!(tree.created_blocks.get(), 1);
+            assert_eq!(tree.repeated_proposals.get(), 0);
+
+            // Proposing the same block again only counts as a repeat
+            tree.propose_block(1, hash(1), hash(2), 1).unwrap();
+            assert_eq!(tree.created_blocks.get(), 1);
+            assert_eq!(tree.repeated_proposals.get(), 1);
+            assert_eq!(tree.proposal_count(hash(2)), Some(2));
+            assert_eq!(tree.proposal_count(hash(3)), None);
+        });
+    }
+
+    #[test]
+    fn test_genesis_parent() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let config = ForkChoiceTreeConfig { genesis_parent: hash(0xee), ..config() };
+            let mut tree = ForkChoiceTree::new(context, config);
+
+            // An all-zeros block is a regular block under a custom sentinel
+            tree.propose_block(1, hash(1), hash(0), 1).unwrap();
+            tree.propose_block(2, hash(0), hash(2), 1).unwrap();
+            assert_eq!(tree.score(hash(0)), Some(2));
+            let finalization = tree.finalize_block_frame().unwrap();
+            assert_eq!(finalization.new_head, hash(2));
+            assert_eq!(finalization.blocks, vec![hash(0), hash(2)]);
+
+            // Scores still stop at the finalized head
+            tree.propose_block(3, hash(2), hash(3), 1).unwrap();
+            assert_eq!(tree.score(hash(3)), Some(1));
+            assert_eq!(tree.score(hash(2)), Some(1));
+        });
+    }
+
+    #[test]
+    fn test_finalization() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut tree = ForkChoiceTree::new(context, config());
+
+            // Linear chain
+            tree.propose_block(1, hash(1), hash(2), 1).unwrap();
+            tree.propose_block(2, hash(2), hash(3), 1).unwrap();
+            assert_eq!(tree.finalize_block_frame().unwrap(), Finalization {
+                frame_number: 2,
+                previous_head: hash(1),
+                new_head: hash(3),
+                blocks: vec![hash(2), hash(3)],
+                reorg: false,
+            });
+
+            // Fork resolved in favor of the heaviest subtree
+            tree.propose_block(3, hash(3), hash(4), 1).unwrap();
+            tree.propose_block(3, hash(3), hash(5), 1).unwrap();
+            tree.propose_block(4, hash(5), hash(6), 1).unwrap();
+            assert_eq!(tree.finalize_block_frame().unwrap(), Finalization {
+                frame_number: 3,
+                previous_head: hash(3),
+                new_head: hash(6),
+                blocks: vec![hash(5), hash(6)],
+                reorg: true,
+            });
+        });
+    }
+
+    #[test]
+    fn test_leaves() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut tree = ForkChoiceTree::new(context, config());
+            assert_eq!(tree.leaves(), vec![hash(1)]);
+            assert_eq!(tree.fork_count(), 0);
+
+            // Two forks: at the genesis block and at block 2
+            tree.propose_block(1, hash(1), hash(2), 1).unwrap();
+            tree.propose_block(1, hash(1), hash(3), 1).unwrap();
+            tree.propose_block(2, hash(2), hash(4), 1).unwrap();
+            tree.propose_block(2, hash(2), hash(5), 1).unwrap();
+
+            let mut leaves = tree.leaves();
+            leaves.sort();
+            assert_eq!(leaves, vec![hash(3), hash(4), hash(5)]);
+            assert_eq!(tree.fork_count(), 2);
+        });
+    }
+
+    #[test]
+    fn test_score_decay() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let decay = ScoreDecay { numerator: 1, denominator: 2 };
+            let mut decayed = ForkChoiceTree::new(
+                context.with_label("decayed"),
+                ForkChoiceTreeConfig { score_decay: Some(decay), ..config() },
+            );
+            let mut undecayed = ForkChoiceTree::new(context.with_label("undecayed"), config());
+
+            for tree in [&mut decayed, &mut undecayed] {
+                // Old proposals on a fork that can't be solved yet
+                tree.propose_block(1, hash(1), hash(2), 1).unwrap();
+                tree.propose_block(2, hash(2), hash(3), 3).unwrap();
+                tree.propose_block(2, hash(2), hash(4), 3).unwrap();
+                assert!(tree.finalize_block_frame().is_err());
+
+                // Recent proposals on a competing branch
+                tree.propose_block(2, hash(2), hash(3), 1).unwrap();
+                tree.propose_block(1, hash(1), hash(5), 5).unwrap();
+            }
+
+            // Only the decayed tree favors the recent branch
+            assert_eq!(decayed.score(hash(2)), Some(4));
+            assert_eq!(decayed.finalize_block_frame().unwrap().new_head, hash(5));
+            assert_eq!(undecayed.score(hash(2)), Some(8));
+            assert_eq!(undecayed.finalize_block_frame().unwrap().new_head, hash(3));
+        });
+    }
+
+    #[test]
+    fn test_is_finalized() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut tree = ForkChoiceTree::new(context, config());
+            tree.propose_block(1, hash(1), hash(2), 2).unwrap();
+            tree.propose_block(2, hash(2), hash(3), 2).unwrap();
+            tree.propose_block(1, hash(1), hash(4), 1).unwrap();
+            tree.finalize_block_frame().unwrap();
+            assert_eq!(tree.finalized_head(), hash(3));
+
+            // Finalized head and its ancestors
+            assert!(tree.is_finalized(hash(3)));
+            assert!(tree.is_finalized(hash(2)));
+            assert!(tree.is_finalized(hash(1)));
+
+            // Sibling fork, unknown and pending blocks
+            assert!(!tree.is_finalized(hash(4)));
+            assert!(!tree.is_finalized(hash(9)));
+            tree.propose_block(3, hash(3), hash(5), 1).unwrap();
+            assert!(!tree.is_finalized(hash(5)));
+        });
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            // The second proposed branch (block 3) is chosen by an attempt failing on a deeper tie
+            let stalled_tree = |label: &str| {
+                let mut tree = ForkChoiceTree::new(context.with_label(label), ForkChoiceTreeConfig {
+                    hysteresis: 2,
+                    ..config()
+                });
+                tree.propose_block(1, hash(1), hash(2), 1).unwrap();
+                tree.propose_block(1, hash(1), hash(3), 4).unwrap();
+                tree.propose_block(2, hash(3), hash(4), 1).unwrap();
+                tree.propose_block(2, hash(3), hash(5), 1).unwrap();
+                assert!(matches!(
+                    tree.finalize_block_frame(),
+                    Err(ForkChoiceTreeError::UnsolvableFork(block_hash)) if block_hash == hash(3)
+                ));
+                tree.propose_block(2, hash(3), hash(4), 1).unwrap();
+                tree
+            };
+
+            // A marginally heavier competitor doesn't replace the incumbent
+            let mut tree = stalled_tree("marginal");
+            tree.propose_block(1, hash(1), hash(2), 7).unwrap();
+            assert_eq!(tree.finalize_block_frame().unwrap().new_head, hash(4));
+
+            // A clearly heavier one does
+            let mut tree = stalled_tree("clear");
+            tree.propose_block(1, hash(1), hash(2), 9).unwrap();
+            assert_eq!(tree.finalize_block_frame().unwrap().new_head, hash(2));
+        });
+    }
+
+    #[test]
+    fn test_prune_below_frame() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut tree = ForkChoiceTree::new(context, config());
+            tree.propose_block(1, hash(1), hash(2), 1).unwrap();
+            tree.finalize_block_frame().unwrap();
+
+            // Finalize a fork (block 3 loses) and extend it in the next frame
+            tree.propose_block(2, hash(2), hash(3), 1).unwrap();
+            tree.propose_block(2, hash(2), hash(4), 1).unwrap();
+            tree.propose_block(3, hash(4), hash(5), 1).unwrap();
+            tree.finalize_block_frame().unwrap();
+            tree.propose_block(4, hash(5), hash(6), 1).unwrap();
+            assert_eq!(tree.finalize_block_frame().unwrap().frame_number, 4);
+
+            // Remove the ancestors older than frame 3 and the losing fork
+            tree.prune_below_frame(3);
+            for n in 1..=3 {
+                assert_eq!(tree.height(hash(n)), None);
+            }
+            for n in 4..=6 {
+                assert_eq!(tree.height(hash(n)), Some(n as u64 - 2));
+            }
+            assert_eq!(tree.children(hash(4)), Some(&[hash(5)][..]));
+
+            // Reject proposals on a pruned ancestry
+            assert!(matches!(
+                tree.propose_block(3, hash(3), hash(7), 1),
+                Err(ForkChoiceTreeError::InvalidBlockParentHash(parent)) if parent == hash(3)
+            ));
+
+            // Branches off a retained ancestor are dropped by the next pruning
+            tree.propose_block(3, hash(4), hash(7), 1).unwrap();
+            tree.propose_block(5, hash(6), hash(8), 1).unwrap();
+            tree.prune_below_frame(3);
+            assert_eq!(tree.height(hash(7)), None);
+            assert_eq!(tree.children(hash(4)), Some(&[hash(5)][..]));
+            assert_eq!(tree.score(hash(8)), Some(1));
+        });
+    }
+
+    #[test]
+    fn test_subtree_weight() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut tree = ForkChoiceTree::new(context, config());
+            tree.propose_block(1, hash(1), hash(2), 1).unwrap();
+            tree.propose_block(2, hash(2), hash(3), 1).unwrap();
+            tree.propose_block(2, hash(2), hash(4), 1).unwrap();
+            tree.propose_block(2, hash(2), hash(3), 1).unwrap();
+            tree.propose_block(3, hash(3), hash(5), 1).unwrap();
+
+            // Match the incremental scores of unit proposals
+            for (n, weight) in [(2, 5), (3, 3), (4, 1), (5, 1)] {
+                assert_eq!(tree.subtree_weight(hash(n)), Some(weight));
+                assert_eq!(tree.score(hash(n)), Some(weight));
+            }
+
+            // The finalized head counts the proposals below it (but isn't scored)
+            assert_eq!(tree.subtree_weight(hash(1)), Some(5));
+            assert_eq!(tree.score(hash(1)), Some(0));
+            assert_eq!(tree.subtree_weight(hash(6)), None);
+
+            // Weighted proposals still count once
+            tree.propose_block(4, hash(5), hash(6), 3).unwrap();
+            assert_eq!(tree.subtree_weight(hash(3)), Some(4));
+            assert_eq!(tree.score(hash(3)), Some(6));
+        });
+    }
+
+    #[test]
+    fn test_orphan_height() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut tree = ForkChoiceTree::new(context, config());
+
+            // Reject blocks arriving before their parent (they aren't buffered)
+            assert!(matches!(
+                tree.propose_block(3, hash(2), hash(3), 1),
+                Err(ForkChoiceTreeError::InvalidBlockParentHash(parent)) if parent == hash(2)
+            ));
+
+            // Once the parent is known, reject the block at the wrong height
+            tree.propose_block(1, hash(1), hash(2), 1).unwrap();
+            assert!(matches!(
+                tree.propose_block(3, hash(2), hash(3), 1),
+                Err(ForkChoiceTreeError::InvalidBlockHeight(3))
+            ));
+            assert_eq!(tree.height(hash(3)), None);
+            assert_eq!(tree.children(hash(2)), Some(&[][..]));
+
+            // Accept it at the right height
+            tree.propose_block(2, hash(2), hash(3), 1).unwrap();
+            assert_eq!(tree.score(hash(2)), Some(2));
+        });
+    }
 }
\ No newline at end of file
@@ -0,0 +1,52 @@
+use commonware_cryptography::{
+    ed25519::PublicKey,
+    sha256::{Digest, Sha256},
+    Hasher,
+};
+
+/// Namespace of the genesis hash, to avoid collisions with other digests.
+const GENESIS_NAMESPACE: &[u8] = b"FCN_GENESIS";
+
+/// Derive the genesis block hash from the chain parameters, so the oracle and the swarm
+/// agree on it (the order of the genesis accounts doesn't matter).
+pub fn genesis_hash(chain_id: u64, genesis_accounts: &[(PublicKey, u64)]) -> Digest {
+    let mut accounts = genesis_accounts.iter().collect::<Vec<_>>();
+    accounts.sort();
+
+    let mut hasher = Sha256::new();
+    hasher.update(GENESIS_NAMESPACE);
+    hasher.update(&chain_id.to_be_bytes());
+    hasher.update(&(accounts.len() as u64).to_be_bytes());
+    for (public, balance) in accounts {
+        hasher.update(public.as_ref());
+        hasher.update(&balance.to_be_bytes());
+    }
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commonware_cryptography::{ed25519::PrivateKey, PrivateKeyExt, Signer};
+
+    #[test]
+    fn test_genesis_hash() {
+        let accounts = (0..3)
+            .map(|seed| (PrivateKey::from_seed(seed).public_key(), 100 * seed))
+            .collect::<Vec<_>>();
+        let hash = genesis_hash(1, &accounts);
+
+        // Same inputs (in any order) yield the same digest
+        assert_eq!(genesis_hash(1, &accounts), hash);
+        let mut reversed = accounts.clone();
+        reversed.reverse();
+        assert_eq!(genesis_hash(1, &reversed), hash);
+
+        // Other parameters differ
+        assert_ne!(genesis_hash(2, &accounts), hash);
+        assert_ne!(genesis_hash(1, &accounts[..2]), hash);
+        let mut funded = accounts;
+        funded[0].1 += 1;
+        assert_ne!(genesis_hash(1, &funded), hash);
+    }
+}
\ No newline at end of file
@@ -1,2 +1,3 @@
 pub mod fork_choice_tree;
+pub mod genesis;
 pub mod mempool;
\ No newline at end of file
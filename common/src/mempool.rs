@@ -1,23 +1,89 @@
-use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque},
+};
 
 use commonware_cryptography::{ed25519::PublicKey, Digestible};
 use commonware_runtime::Metrics;
 
-use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::{counter::Counter, gauge::Gauge};
 
-/// The maximum number of transactions a single account can have in the mempool.
+/// The default maximum number of transactions a single account can have in the mempool.
 const MAX_BACKLOG: usize = 16;
 
-/// The maximum number of transactions in the mempool.
+/// The default maximum number of transactions in the mempool.
 const MAX_TRANSACTIONS: usize = 32_768;
 
+/// The default maximum number of distinct accounts in the mempool.
+const MAX_ACCOUNTS: usize = 4_096;
+
 pub trait MempoolTransaction : Digestible {
     fn public_key(&self) -> PublicKey;
     fn nonce(&self) -> u64;
+
+    /// Priority of the transaction (e.g. its fee) when draining by priority.
+    fn priority(&self) -> u64 {
+        0
+    }
+
+    /// Whether the transaction can no longer be included at the given height/time.
+    fn is_expired(&self, _now: u64) -> bool {
+        false
+    }
+}
+
+/// Limits of a [Mempool].
+#[derive(Clone, Copy, Debug)]
+pub struct MempoolConfig {
+    /// The maximum number of transactions a single account can have in the mempool.
+    pub max_backlog: usize,
+    /// The maximum number of transactions in the mempool.
+    pub max_transactions: usize,
+    /// The maximum number of distinct accounts with transactions in the mempool.
+    pub max_accounts: usize,
+    /// The maximum distance between the nonce of a transaction and the next nonce of its
+    /// account (checked by [Mempool::add_expecting], unlimited if unset).
+    pub max_nonce_gap: Option<u64>,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        Self {
+            max_backlog: MAX_BACKLOG,
+            max_transactions: MAX_TRANSACTIONS,
+            max_accounts: MAX_ACCOUNTS,
+            max_nonce_gap: None,
+        }
+    }
+}
+
+/// The outcome of adding a transaction to the mempool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddResult {
+    /// The transaction was added.
+    Added,
+    /// A transaction with the same digest is already in the mempool.
+    Duplicate,
+    /// A transaction with the same nonce is already tracked for the account.
+    NonceTaken,
+    /// The mempool is full and the transaction doesn't pay more than the cheapest one.
+    Full,
+    /// The account backlog is full and the transaction is the furthest in the future.
+    BacklogFull,
+    /// The nonce of the transaction was already processed.
+    Stale,
+    /// The mempool already tracks the maximum number of accounts and the sender is not one of them.
+    TooManyAccounts,
+    /// The nonce of the transaction is too far ahead of the next nonce of the account.
+    NonceGap,
+    /// The sender was rejected by the admission check of the mempool.
+    UnknownSender,
 }
 
 /// A mempool for transactions.
 pub struct Mempool<T: MempoolTransaction> {
+    config: MempoolConfig,
+
     transactions: HashMap<T::Digest, T>,
     tracked: HashMap<PublicKey, BTreeMap<u64, T::Digest>>,
     /// We store the public keys of the transactions to be processed next (rather than transactions
@@ -25,17 +91,28 @@ pub struct Mempool<T: MempoolTransaction> {
     /// already been processed) and should just try return the transaction with the lowest nonce we
     /// are currently tracking.
     queue: VecDeque<PublicKey>,
+    /// Transactions ordered by fee, used to find the cheapest transaction to evict when the
+    /// mempool is full.
+    fees: BTreeSet<(u64, T::Digest)>,
+    /// Number of transactions served in a row for each turn of weighted accounts.
+    weights: HashMap<PublicKey, u64>,
+    /// Number of transactions served so far in the turn of the account at the front of the queue.
+    turn: u64,
+    /// Check of the senders allowed to add transactions (all senders if unset).
+    admission: Option<Box<dyn Fn(&PublicKey) -> bool + Send>>,
 
     unique: Gauge,
     accounts: Gauge,
+    unknown_senders: Counter,
 }
 
 impl <T: MempoolTransaction> Mempool<T> {
     /// Create a new mempool.
-    pub fn new(context: impl Metrics) -> Self {
+    pub fn new(context: impl Metrics, config: MempoolConfig) -> Self {
         // Initialize metrics
         let unique = Gauge::default();
         let accounts = Gauge::default();
+        let unknown_senders = Counter::default();
         context.register(
             "transactions",
             "Number of transactions in the mempool",
@@ -46,50 +123,183 @@ impl <T: MempoolTransaction> Mempool<T> {
             "Number of accounts in the mempool",
             accounts.clone(),
         );
+        context.register(
+            "unknown_senders",
+            "Number of transactions rejected by the admission check",
+            unknown_senders.clone(),
+        );
 
         // Initialize mempool
         Self {
+            config,
+
             transactions: HashMap::new(),
             tracked: HashMap::new(),
             queue: VecDeque::new(),
+            fees: BTreeSet::new(),
+            weights: HashMap::new(),
+            turn: 0,
+            admission: None,
 
             unique,
             accounts,
+            unknown_senders,
+        }
+    }
+
+    /// Rebuild a mempool from a snapshot.
+    pub fn restore(context: impl Metrics, config: MempoolConfig, txs: Vec<T>) -> Self {
+        let mut mempool = Self::new(context, config);
+        for tx in txs {
+            mempool.add(tx);
         }
+        mempool
+    }
+
+    /// Get all transactions in the mempool, ordered by the queue position of their
+    /// account and then by nonce (the order in which [Mempool::restore] expects them).
+    pub fn snapshot(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut seen = HashSet::new();
+        let mut txs = Vec::with_capacity(self.transactions.len());
+        for address in &self.queue {
+            // The queue may contain stale or repeated addresses
+            if !seen.insert(address) {
+                continue;
+            }
+            let Some(tracked) = self.tracked.get(address) else {
+                continue;
+            };
+            for digest in tracked.values() {
+                txs.push(self.transactions[digest].clone());
+            }
+        }
+        txs
     }
 
     /// Add a transaction to the mempool.
-    pub fn add(&mut self, tx: T) {
-        // If there are too many transactions, ignore
-        if self.transactions.len() >= MAX_TRANSACTIONS {
-            return;
+    pub fn add(&mut self, tx: T) -> AddResult {
+        let result = self.insert(tx);
+
+        // Update metrics
+        self.unique.set(self.transactions.len() as i64);
+        self.accounts.set(self.tracked.len() as i64);
+
+        result
+    }
+
+    /// Add a transaction to the mempool given the next nonce of its account, rejecting it
+    /// if it was already processed or is too far in the future.
+    pub fn add_expecting(&mut self, tx: T, next_nonce: u64) -> AddResult {
+        if tx.nonce() < next_nonce {
+            return AddResult::Stale;
+        }
+        if let Some(max_nonce_gap) = self.config.max_nonce_gap {
+            if tx.nonce() - next_nonce > max_nonce_gap {
+                return AddResult::NonceGap;
+            }
         }
+        self.add(tx)
+    }
+
+    /// Add a batch of transactions to the mempool, updating metrics only once.
+    pub fn add_many(&mut self, txs: impl IntoIterator<Item = T>) -> Vec<AddResult> {
+        let results = txs.into_iter().map(|tx| self.insert(tx)).collect();
 
+        // Update metrics
+        self.unique.set(self.transactions.len() as i64);
+        self.accounts.set(self.tracked.len() as i64);
+
+        results
+    }
+
+    /// Re-admit transactions reverted by a reorg, given the next nonce of their accounts
+    /// after the rollback (accounts missing from `next_nonces` are assumed to be at nonce 0).
+    pub fn readmit(
+        &mut self,
+        txs: impl IntoIterator<Item = T>,
+        next_nonces: &BTreeMap<PublicKey, u64>,
+    ) -> Vec<AddResult> {
+        // Drop tracked transactions that were processed again after the rollback
+        for (public, next_nonce) in next_nonces {
+            self.prune(public, *next_nonce);
+        }
+
+        // Only re-admit transactions that can still be executed
+        let results = txs
+            .into_iter()
+            .map(|tx| {
+                let next_nonce = next_nonces.get(&tx.public_key()).copied().unwrap_or(0);
+                if tx.nonce() < next_nonce {
+                    return AddResult::Stale;
+                }
+                self.insert(tx)
+            })
+            .collect();
+
+        // Update metrics
+        self.unique.set(self.transactions.len() as i64);
+        self.accounts.set(self.tracked.len() as i64);
+
+        results
+    }
+
+    fn insert(&mut self, tx: T) -> AddResult {
         // Determine if duplicate
         let digest = tx.digest();
         if self.transactions.contains_key(&digest) {
             // If we already have a transaction with this digest, we don't need to track it
-            return;
+            return AddResult::Duplicate;
         }
 
-        // Track the transaction
+        // Reject senders not allowed by the admission check
         let public = tx.public_key();
-        let entry = self.tracked.entry(public.clone()).or_default();
+        if self.admission.as_ref().is_some_and(|admit| !admit(&public)) {
+            self.unknown_senders.inc();
+            return AddResult::UnknownSender;
+        }
 
         // If there already exists a transaction at some nonce, return
-        if entry.contains_key(&tx.nonce()) {
-            return;
+        match self.tracked.get(&public) {
+            Some(tracked) if tracked.contains_key(&tx.nonce()) => return AddResult::NonceTaken,
+            Some(_) => {}
+            None => {
+                // If there are too many accounts, reject transactions from new ones
+                if self.tracked.len() >= self.config.max_accounts {
+                    return AddResult::TooManyAccounts;
+                }
+            }
+        }
+
+        // If there are too many transactions, make room by evicting the lowest fee transaction
+        // (only if the incoming transaction pays more)
+        if self.transactions.len() >= self.config.max_transactions {
+            let Some(&(fee, lowest)) = self.fees.first() else {
+                return AddResult::Full;
+            };
+            if tx.priority() <= fee {
+                return AddResult::Full;
+            }
+            self.evict(&lowest);
         }
 
         // Insert the transaction into the mempool
+        let entry = self.tracked.entry(public.clone()).or_default();
         assert!(entry.insert(tx.nonce(), digest).is_none());
+        self.fees.insert((tx.priority(), digest));
         self.transactions.insert(digest, tx);
 
         // If there are too many transactions, remove the furthest in the future
         let entries = entry.len();
-        if entries > MAX_BACKLOG {
+        if entries > self.config.max_backlog {
             let (_, future) = entry.pop_last().unwrap();
-            self.transactions.remove(&future);
+            let tx = self.transactions.remove(&future).unwrap();
+            self.fees.remove(&(tx.priority(), future));
+            if future == digest {
+                return AddResult::BacklogFull;
+            }
         }
 
         // Add to queue if this is the first entry (otherwise the public key will already be
@@ -98,13 +308,101 @@ impl <T: MempoolTransaction> Mempool<T> {
             self.queue.push_back(public);
         }
 
+        AddResult::Added
+    }
+
+    /// Remove a single transaction from the mempool.
+    fn evict(&mut self, digest: &T::Digest) {
+        let Some(tx) = self.transactions.remove(digest) else {
+            return;
+        };
+        self.fees.remove(&(tx.priority(), *digest));
+
+        // Stop tracking the account if this was its last transaction (the queue is
+        // pruned lazily)
+        let public = tx.public_key();
+        if let Some(tracked) = self.tracked.get_mut(&public) {
+            tracked.remove(&tx.nonce());
+            if tracked.is_empty() {
+                self.tracked.remove(&public);
+            }
+        }
+    }
+
+    /// Only accept transactions from senders passing the given check.
+    pub fn set_admission(&mut self, admit: impl Fn(&PublicKey) -> bool + Send + 'static) {
+        self.admission = Some(Box::new(admit));
+    }
+
+    /// Set the number of transactions served in a row for each turn of an account (1 by default).
+    pub fn set_weight(&mut self, public: PublicKey, weight: u64) {
+        self.weights.insert(public, weight.max(1));
+    }
+
+    /// Check if the mempool holds a transaction with the given digest.
+    pub fn contains(&self, digest: &T::Digest) -> bool {
+        self.transactions.contains_key(digest)
+    }
+
+    /// Get the number of transactions in the mempool.
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    /// Check if the mempool holds no transactions.
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// Get every tracked account with its number of pending transactions.
+    pub fn accounts(&self) -> Vec<(PublicKey, usize)> {
+        self.tracked
+            .iter()
+            .map(|(public, tracked)| (public.clone(), tracked.len()))
+            .collect()
+    }
+
+    /// Remove stale and repeated accounts from the queue (keeping the order of the others).
+    pub fn compact(&mut self) {
+        let mut seen = HashSet::new();
+        self.queue.retain(|public| self.tracked.contains_key(public) && seen.insert(public.clone()));
+    }
+
+    /// Remove all transactions from the mempool.
+    pub fn clear(&mut self) {
+        self.transactions.clear();
+        self.tracked.clear();
+        self.queue.clear();
+        self.fees.clear();
+        self.turn = 0;
+
+        // Update metrics
+        self.unique.set(0);
+        self.accounts.set(0);
+    }
+
+    /// Retain transactions for a given account with a minimum nonce.
+    pub fn retain(&mut self, public: &PublicKey, min: u64) {
+        self.prune(public, min);
+
+        // Update metrics
+        self.unique.set(self.transactions.len() as i64);
+        self.accounts.set(self.tracked.len() as i64);
+    }
+
+    /// Retain transactions with a minimum nonce for each of the given accounts, updating
+    /// metrics only once.
+    pub fn retain_all(&mut self, min_nonces: &BTreeMap<PublicKey, u64>) {
+        for (public, min) in min_nonces {
+            self.prune(public, *min);
+        }
+
         // Update metrics
         self.unique.set(self.transactions.len() as i64);
         self.accounts.set(self.tracked.len() as i64);
     }
 
-    /// Retain transactions for a given account with a minimum nonce.
-    pub fn retain(&mut self, public: &PublicKey, min: u64) {
+    fn prune(&mut self, public: &PublicKey, min: u64) {
         // Remove any items no longer present
         let Some(tracked) = self.tracked.get_mut(public) else {
             return;
@@ -116,7 +414,8 @@ impl <T: MempoolTransaction> Mempool<T> {
             if nonce >= &min {
                 break false;
             }
-            self.transactions.remove(digest);
+            let tx = self.transactions.remove(digest).unwrap();
+            self.fees.remove(&(tx.priority(), *digest));
             tracked.pop_first();
         };
 
@@ -124,37 +423,53 @@ impl <T: MempoolTransaction> Mempool<T> {
         if remove {
             self.tracked.remove(public);
         }
-
-        // Update metrics
-        self.unique.set(self.transactions.len() as i64);
-        self.accounts.set(self.tracked.len() as i64);
     }
 
-    /// Get the next transaction to process from the mempool.
-    pub fn next(&mut self) -> Option<T> {
+    /// Get the next transaction to process from the mempool, discarding expired
+    /// transactions along the way.
+    pub fn next(&mut self, now: u64) -> Option<T> {
         let tx = loop {
             // Get the transaction with the lowest nonce
-            let address = self.queue.pop_front()?;
+            let Some(address) = self.queue.pop_front() else {
+                break None;
+            };
+            let weight = self.weights.get(&address).copied().unwrap_or(1);
             let Some(tracked) = self.tracked.get_mut(&address) else {
                 // We don't prune the queue when we drop a transaction, so we may need to
                 // read through some untracked addresses.
+                self.turn = 0;
                 continue;
             };
             let Some((_, digest)) = tracked.pop_first() else {
+                self.turn = 0;
                 continue;
             };
 
-            // If the address still has transactions, add it to the end of the queue (to
-            // ensure everyone gets a chance to process their transactions)
+            // If the address still has transactions, keep it at the front of the queue until
+            // its turn is over and then add it to the end of the queue (to ensure everyone gets
+            // a chance to process their transactions)
             if !tracked.is_empty() {
-                self.queue.push_back(address);
+                self.turn += 1;
+                if self.turn < weight {
+                    self.queue.push_front(address);
+                } else {
+                    self.turn = 0;
+                    self.queue.push_back(address);
+                }
             } else {
                 // If the address has no transactions, remove it from the tracked map
+                self.turn = 0;
                 self.tracked.remove(&address);
             }
 
             // Remove the transaction from the mempool
             let tx = self.transactions.remove(&digest).unwrap();
+            self.fees.remove(&(tx.priority(), digest));
+
+            // Skip expired transactions
+            if tx.is_expired(now) {
+                continue;
+            }
             break Some(tx);
         };
 
@@ -164,4 +479,433 @@ impl <T: MempoolTransaction> Mempool<T> {
 
         tx
     }
+
+    /// Drain up to `max` transactions from the mempool, highest priority first (and lowest
+    /// nonce on ties), without ever serving a transaction before a lower nonce of the same
+    /// account.
+    pub fn drain_by_priority(&mut self, max: usize) -> Vec<T> {
+        // Only the lowest nonce transaction of each account is eligible
+        let mut heap = BinaryHeap::new();
+        for (public, tracked) in &self.tracked {
+            if let Some((nonce, digest)) = tracked.first_key_value() {
+                let priority = self.transactions[digest].priority();
+                heap.push((priority, Reverse(*nonce), public.clone()));
+            }
+        }
+
+        let mut txs = Vec::new();
+        while txs.len() < max {
+            let Some((_, _, public)) = heap.pop() else {
+                break;
+            };
+            let tracked = self.tracked.get_mut(&public).unwrap();
+            let (_, digest) = tracked.pop_first().unwrap();
+            let tx = self.transactions.remove(&digest).unwrap();
+            self.fees.remove(&(tx.priority(), digest));
+            txs.push(tx);
+
+            // Make the next transaction of the account eligible
+            let next = tracked.first_key_value().map(|(nonce, digest)| (*nonce, *digest));
+            match next {
+                Some((nonce, digest)) => {
+                    let priority = self.transactions[&digest].priority();
+                    heap.push((priority, Reverse(nonce), public));
+                }
+                None => {
+                    self.tracked.remove(&public);
+                }
+            }
+        }
+
+        // Update metrics
+        self.unique.set(self.transactions.len() as i64);
+        self.accounts.set(self.tracked.len() as i64);
+
+        txs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commonware_cryptography::{
+        ed25519::PrivateKey,
+        sha256::{Digest, Sha256},
+        Hasher, PrivateKeyExt, Signer,
+    };
+    use commonware_runtime::{deterministic, Runner};
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct MockTransaction {
+        public_key: PublicKey,
+        nonce: u64,
+        fee: u64,
+        expiry: Option<u64>,
+    }
+
+    impl MockTransaction {
+        fn new(account: u64, nonce: u64) -> Self {
+            Self {
+                public_key: PrivateKey::from_seed(account).public_key(),
+                nonce,
+                fee: 0,
+                expiry: None,
+            }
+        }
+
+        fn with_fee(mut self, fee: u64) -> Self {
+            self.fee = fee;
+            self
+        }
+    }
+
+    impl Digestible for MockTransaction {
+        type Digest = Digest;
+
+        fn digest(&self) -> Digest {
+            let mut hasher = Sha256::new();
+            hasher.update(self.public_key.as_ref());
+            hasher.update(&self.nonce.to_be_bytes());
+            hasher.update(&self.fee.to_be_bytes());
+            hasher.finalize()
+        }
+    }
+
+    impl MempoolTransaction for MockTransaction {
+        fn public_key(&self) -> PublicKey {
+            self.public_key.clone()
+        }
+
+        fn nonce(&self) -> u64 {
+            self.nonce
+        }
+
+        fn priority(&self) -> u64 {
+            self.fee
+        }
+
+        fn is_expired(&self, now: u64) -> bool {
+            self.expiry.is_some_and(|expiry| now > expiry)
+        }
+    }
+
+    #[test]
+    fn test_contains() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut mempool = Mempool::new(context, MempoolConfig::default());
+            let tx = MockTransaction::new(0, 0);
+            assert!(!mempool.contains(&tx.digest()));
+
+            assert_eq!(mempool.add(tx.clone()), AddResult::Added);
+            assert!(mempool.contains(&tx.digest()));
+
+            // Served transactions leave the mempool
+            assert_eq!(mempool.next(0), Some(tx.clone()));
+            assert!(!mempool.contains(&tx.digest()));
+        });
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut mempool = Mempool::new(context.with_label("original"), MempoolConfig::default());
+            for (account, nonce) in [(0, 1), (1, 0), (0, 0), (2, 3), (1, 1)] {
+                assert_eq!(mempool.add(MockTransaction::new(account, nonce)), AddResult::Added);
+            }
+
+            // The restored mempool serves transactions in the same order
+            let snapshot = mempool.snapshot();
+            assert_eq!(snapshot.len(), 5);
+            let mut restored = Mempool::restore(context.with_label("restored"), MempoolConfig::default(), snapshot);
+            for _ in 0..5 {
+                let tx = mempool.next(0);
+                assert!(tx.is_some());
+                assert_eq!(restored.next(0), tx);
+            }
+            assert_eq!(restored.next(0), None);
+        });
+    }
+
+    #[test]
+    fn test_clear() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut mempool = Mempool::new(context, MempoolConfig::default());
+            assert!(mempool.is_empty());
+            for (account, nonce) in [(0, 0), (0, 1), (1, 0)] {
+                mempool.add(MockTransaction::new(account, nonce));
+            }
+            assert_eq!(mempool.len(), 3);
+            assert!(!mempool.is_empty());
+
+            mempool.clear();
+            assert_eq!(mempool.len(), 0);
+            assert!(mempool.is_empty());
+            assert_eq!(mempool.unique.get(), 0);
+            assert_eq!(mempool.accounts.get(), 0);
+            assert_eq!(mempool.next(0), None);
+        });
+    }
+
+    #[test]
+    fn test_drain_by_priority() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut mempool = Mempool::new(context, MempoolConfig::default());
+            for (account, nonce, fee) in [(0, 0, 1), (0, 1, 10), (1, 0, 5), (2, 0, 3)] {
+                mempool.add(MockTransaction::new(account, nonce).with_fee(fee));
+            }
+
+            // The highest fee is served last, as it waits for a lower nonce of its account
+            let served = mempool.drain_by_priority(10)
+                .into_iter()
+                .map(|tx| (tx.fee, tx.nonce))
+                .collect::<Vec<_>>();
+            assert_eq!(served, vec![(5, 0), (3, 0), (1, 0), (10, 1)]);
+            assert!(mempool.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_evict_lowest_fee() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let config = MempoolConfig { max_transactions: 3, ..MempoolConfig::default() };
+            let mut mempool = Mempool::new(context, config);
+            let cheapest = MockTransaction::new(1, 0).with_fee(1);
+            for tx in [MockTransaction::new(0, 0).with_fee(2), cheapest.clone(), MockTransaction::new(2, 0).with_fee(3)] {
+                assert_eq!(mempool.add(tx), AddResult::Added);
+            }
+
+            // Reject transactions not paying more than the cheapest one
+            assert_eq!(mempool.add(MockTransaction::new(3, 0).with_fee(1)), AddResult::Full);
+
+            // Evict the cheapest transaction for a higher fee
+            let tx = MockTransaction::new(3, 0).with_fee(5);
+            assert_eq!(mempool.add(tx.clone()), AddResult::Added);
+            assert_eq!(mempool.len(), 3);
+            assert!(mempool.contains(&tx.digest()));
+            assert!(!mempool.contains(&cheapest.digest()));
+        });
+    }
+
+    #[test]
+    fn test_add_many() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut mempool = Mempool::new(context, MempoolConfig::default());
+            let mut txs = (0..100).map(|i| MockTransaction::new(i % 10, i / 10)).collect::<Vec<_>>();
+            txs.push(txs[0].clone());
+
+            // Report the outcome of each transaction
+            let results = mempool.add_many(txs);
+            assert_eq!(results.len(), 101);
+            assert!(results[..100].iter().all(|result| *result == AddResult::Added));
+            assert_eq!(results[100], AddResult::Duplicate);
+
+            // Gauges reflect the final state
+            assert_eq!(mempool.len(), 100);
+            assert_eq!(mempool.unique.get(), 100);
+            assert_eq!(mempool.accounts.get(), 10);
+        });
+    }
+
+    #[test]
+    fn test_accounts() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut mempool = Mempool::new(context, MempoolConfig::default());
+            for (account, depth) in [(0, 1), (1, 3), (2, 2)] {
+                for nonce in 0..depth {
+                    mempool.add(MockTransaction::new(account, nonce));
+                }
+            }
+
+            let mut accounts = mempool.accounts();
+            accounts.sort();
+            let mut expected = [(0, 1), (1, 3), (2, 2)]
+                .map(|(account, depth)| (MockTransaction::new(account, 0).public_key, depth))
+                .to_vec();
+            expected.sort();
+            assert_eq!(accounts, expected);
+        });
+    }
+
+    #[test]
+    fn test_readmit() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut mempool = Mempool::new(context, MempoolConfig::default());
+            let txs = (0..4).map(|nonce| MockTransaction::new(0, nonce)).collect::<Vec<_>>();
+            mempool.add_many(txs.clone());
+
+            // Serve the first transactions (included in a block later reverted)
+            let served = (0..3).map(|_| mempool.next(0).unwrap()).collect::<Vec<_>>();
+            let other = MockTransaction::new(1, 0);
+            mempool.add(other.clone());
+
+            // After the rollback, the new chain processed the first nonce of both accounts
+            let next_nonces = BTreeMap::from([
+                (txs[0].public_key.clone(), 1),
+                (other.public_key.clone(), 1),
+            ]);
+            let results = mempool.readmit(served, &next_nonces);
+            assert_eq!(results, vec![AddResult::Stale, AddResult::Added, AddResult::Added]);
+            assert!(!mempool.contains(&other.digest()));
+
+            // Stale transactions are never served again
+            let nonces = std::iter::from_fn(|| mempool.next(0))
+                .map(|tx| tx.nonce)
+                .collect::<Vec<_>>();
+            assert_eq!(nonces, vec![1, 2, 3]);
+        });
+    }
+
+    #[test]
+    fn test_retain_all() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut mempool = Mempool::new(context, MempoolConfig::default());
+            for account in 0..3 {
+                mempool.add_many((0..3).map(|nonce| MockTransaction::new(account, nonce)));
+            }
+
+            // Prune the three accounts at once (the last one entirely)
+            let public = |account| MockTransaction::new(account, 0).public_key;
+            let min_nonces = BTreeMap::from([(public(0), 1), (public(1), 2), (public(2), 3)]);
+            mempool.retain_all(&min_nonces);
+            assert_eq!(mempool.len(), 3);
+            assert_eq!(mempool.unique.get(), 3);
+            assert_eq!(mempool.accounts.get(), 2);
+
+            // Accounts keep their turn in the queue
+            let served = std::iter::from_fn(|| mempool.next(0))
+                .map(|tx| (tx.public_key, tx.nonce))
+                .collect::<Vec<_>>();
+            assert_eq!(served, vec![(public(0), 1), (public(1), 2), (public(0), 2)]);
+        });
+    }
+
+    #[test]
+    fn test_expired() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut mempool = Mempool::new(context, MempoolConfig::default());
+            let expired = MockTransaction { expiry: Some(5), ..MockTransaction::new(0, 0) };
+            let fresh = MockTransaction { expiry: Some(10), ..MockTransaction::new(1, 0) };
+            mempool.add(expired.clone());
+            mempool.add(fresh.clone());
+
+            // Skip (and discard) the expired transaction
+            assert_eq!(mempool.next(6), Some(fresh));
+            assert_eq!(mempool.next(6), None);
+            assert!(!mempool.contains(&expired.digest()));
+            assert!(mempool.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_weighted_round_robin() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let config = MempoolConfig { max_backlog: 64, ..MempoolConfig::default() };
+            let mut mempool = Mempool::new(context, config);
+            let weighted = MockTransaction::new(0, 0).public_key;
+            mempool.set_weight(weighted.clone(), 3);
+            for account in 0..2 {
+                mempool.add_many((0..40).map(|nonce| MockTransaction::new(account, nonce)));
+            }
+
+            // The weighted account is served three times in a row on each turn
+            let served = (0..40)
+                .map(|_| mempool.next(0).unwrap().public_key == weighted)
+                .collect::<Vec<_>>();
+            assert_eq!(served[..8], [true, true, true, false, true, true, true, false]);
+            assert_eq!(served.iter().filter(|weighted| **weighted).count(), 30);
+        });
+    }
+
+    #[test]
+    fn test_compact() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut mempool = Mempool::new(context, MempoolConfig::default());
+            for account in 0..4 {
+                mempool.add_many((0..2).map(|nonce| MockTransaction::new(account, nonce)));
+            }
+
+            // Drain two accounts (the queue is pruned lazily)
+            for account in 0..2 {
+                mempool.retain(&MockTransaction::new(account, 0).public_key, 2);
+            }
+            assert_eq!(mempool.queue.len(), 4);
+
+            // Compacting only keeps the live accounts (in order)
+            mempool.compact();
+            let live = (2..4).map(|account| MockTransaction::new(account, 0).public_key).collect::<Vec<_>>();
+            assert_eq!(mempool.queue.iter().cloned().collect::<Vec<_>>(), live);
+            assert_eq!(mempool.next(0).unwrap().public_key, live[0]);
+        });
+    }
+
+    #[test]
+    fn test_max_accounts() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut mempool = Mempool::new(context, MempoolConfig {
+                max_accounts: 3,
+                ..MempoolConfig::default()
+            });
+            for account in 0..3 {
+                assert_eq!(mempool.add(MockTransaction::new(account, 0)), AddResult::Added);
+            }
+
+            // Reject transactions from new accounts, but not from tracked ones
+            assert_eq!(mempool.add(MockTransaction::new(3, 0)), AddResult::TooManyAccounts);
+            assert_eq!(mempool.add(MockTransaction::new(0, 1)), AddResult::Added);
+            assert_eq!(mempool.accounts().len(), 3);
+
+            // Accept new accounts once a tracked one is emptied
+            mempool.retain(&MockTransaction::new(1, 0).public_key, 1);
+            assert_eq!(mempool.add(MockTransaction::new(3, 0)), AddResult::Added);
+        });
+    }
+
+    #[test]
+    fn test_max_nonce_gap() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut mempool = Mempool::new(context, MempoolConfig {
+                max_nonce_gap: Some(2),
+                ..MempoolConfig::default()
+            });
+
+            // Reject transactions too far ahead of the next nonce
+            assert_eq!(mempool.add_expecting(MockTransaction::new(0, 110), 10), AddResult::NonceGap);
+            assert_eq!(mempool.add_expecting(MockTransaction::new(0, 13), 10), AddResult::NonceGap);
+            assert_eq!(mempool.add_expecting(MockTransaction::new(0, 12), 10), AddResult::Added);
+
+            // Reject processed transactions
+            assert_eq!(mempool.add_expecting(MockTransaction::new(0, 9), 10), AddResult::Stale);
+            assert_eq!(mempool.len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_admission() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut mempool = Mempool::new(context, MempoolConfig::default());
+            let known = MockTransaction::new(0, 0).public_key;
+            mempool.set_admission(move |public| *public == known);
+
+            // Reject transactions from unknown senders (counting them)
+            assert_eq!(mempool.add(MockTransaction::new(1, 0)), AddResult::UnknownSender);
+            assert_eq!(mempool.add(MockTransaction::new(0, 0)), AddResult::Added);
+            assert_eq!(mempool.len(), 1);
+            assert_eq!(mempool.unknown_senders.get(), 1);
+        });
+    }
 }
\ No newline at end of file
@@ -1,31 +1,74 @@
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use commonware_codec::Decode;
+use commonware_codec::{Decode, Encode, RangeCfg, Read};
 use commonware_cryptography::{
     ed25519::{PrivateKey, PublicKey}, sha256::Digest, Signer
 };
-use commonware_runtime::{Clock, Handle, Metrics, Spawner, Storage};
+use commonware_runtime::{Blob, Clock, Handle, Metrics, Spawner, Storage};
 use commonware_p2p::{Sender, Receiver, Recipients};
 use commonware_broadcast::{buffered, Broadcaster};
 use commonware_macros::select;
 
+use futures::{channel::mpsc, future, StreamExt};
 use rand::{CryptoRng, Rng};
 use governor::clock::Clock as GClock;
+use prometheus_client::metrics::{counter::Counter, gauge::Gauge};
+use tracing::{debug, info, instrument, warn, Span};
 
-use fcn_common::mempool::Mempool;
+use fcn_common::{
+    fork_choice_tree::ScoreDecay,
+    mempool::{AddResult, Mempool, MempoolConfig},
+};
 use crate::{
-    execution::{State,  execute_state_transition},
-    types::{Transaction, Event},
+    execution::{Config as StateConfig, FinalizationTrigger, State, StateTransitionResult, execute_state_transition},
+    mailbox::{Mailbox, Message, Query, QueryResponse, ShutdownHandle},
+    types::{Transaction, Event, MintedBlock},
     wire::MessageEvent,
 };
 
+/// The maximum number of attempts to broadcast a single event.
+const BROADCAST_ATTEMPTS: usize = 3;
+
+/// The delay between two attempts to broadcast the same event.
+const BROADCAST_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// The number of consecutive failed broadcasts after which the event buffer is considered
+/// saturated (and events are no longer retried, so minting doesn't fall behind).
+const BROADCAST_SATURATION: i64 = 16;
+
+/// The maximum number of pending queries in the actor mailbox.
+const MAILBOX_SIZE: usize = 1024;
+
+/// Storage partition of the actor.
+const STORAGE_PARTITION: &str = "oracle";
+
+/// Name of the blob logging the transactions executed by every minted block (replayed on
+/// restart to rebuild the state).
+const BLOCKS_BLOB: &[u8] = b"blocks";
+
 pub struct Config {    
     pub genesis_block_hash: Digest,
+    pub genesis_parent: Digest,
+    pub max_fork_depth: Option<u64>,
+    pub score_decay: Option<ScoreDecay>,
+    pub hysteresis: u64,
+    pub frame_retention: Option<u64>,
+    pub genesis_builders: Vec<PublicKey>,
 
     pub block_period: Duration,
-    pub finalize_frame_block_prosposal_min: u64,
+    pub align_to_epoch: bool,
+    pub finalization_trigger: FinalizationTrigger,
+    pub max_finalization_attempts: u64,
+    pub max_proposals_per_builder_per_frame: u64,
+    pub max_builder_stake: u64,
+    pub mempool: MempoolConfig,
 
     pub event_signer: PrivateKey,
+    /// Peers receiving finalized frames (all peers if empty).
+    pub frame_recipients: Vec<PublicKey>,
+    /// Seed of the actor randomness (nothing in the actor draws randomness yet, so it has
+    /// no effect).
+    pub rng_seed: Option<u64>,
 }
 
 pub struct Actor<
@@ -33,20 +76,38 @@ pub struct Actor<
 > {
     context: E,
 
-    buffer: buffered::Engine<E, PublicKey, MessageEvent>,
+    buffer: Option<buffered::Engine<E, PublicKey, MessageEvent>>,
     buffer_mailbox: buffered::Mailbox<PublicKey, MessageEvent>,
+    queries: Option<mpsc::Receiver<Message>>,
     
     block_period: Duration,
+    align_to_epoch: bool,
+    frame_recipients: Vec<PublicKey>,
     mempool: Mempool<Transaction>,
     
     state: State,
+    blocks: E::Blob,
+    blocks_size: u64,
     block_number: u64,
+    block_hash: Digest,
+    next_block: SystemTime,
+    frame_started: SystemTime,
+    last_finalized_frame: u64,
+
+    broadcast_failures: Counter,
+    broadcast_backlog: Gauge,
+    frame_proposals: Gauge,
+    finalization_latency: Gauge,
+    fork_count: Gauge,
 }
 
 impl<
     E: Clock + GClock + Rng + CryptoRng + Spawner + Storage + Metrics,
 >Actor<E> {
-    pub async fn new(context: E, config: Config) -> Self {
+    pub async fn new(context: E, config: Config) -> (Self, Mailbox) {
+        // Check config
+        assert!(!config.block_period.is_zero(), "block period must be positive");
+
         let (buffer, buffer_mailbox) = buffered::Engine::new(
             context.with_label("buffer"),
             buffered::Config{
@@ -58,95 +119,989 @@ impl<
             }
         );
         
-        let mempool = Mempool::<Transaction>::new(context.with_label("mempool"));
+        let (sender, queries) = mpsc::channel(MAILBOX_SIZE);
+        let mempool = Mempool::<Transaction>::new(context.with_label("mempool"), config.mempool);
         
-        let state = State::new(
-            config.genesis_block_hash,
-            config.finalize_frame_block_prosposal_min
+        let mut state = State::new(context.with_label("state"), StateConfig {
+            genesis_block_hash: config.genesis_block_hash,
+            genesis_parent: config.genesis_parent,
+            max_fork_depth: config.max_fork_depth,
+            score_decay: config.score_decay,
+            hysteresis: config.hysteresis,
+            frame_retention: config.frame_retention,
+            genesis_builders: config.genesis_builders,
+
+            finalization_trigger: config.finalization_trigger,
+            max_finalization_attempts: config.max_finalization_attempts,
+            max_proposals_per_builder_per_frame: config.max_proposals_per_builder_per_frame,
+            max_builder_stake: config.max_builder_stake,
+        });
+
+        let broadcast_failures = Counter::default();
+        context.register(
+            "broadcast_failures",
+            "Number of event broadcasts that failed after all attempts",
+            broadcast_failures.clone(),
+        );
+        let broadcast_backlog = Gauge::default();
+        context.register(
+            "broadcast_backlog",
+            "Number of consecutive event broadcasts that failed after all attempts",
+            broadcast_backlog.clone(),
         );
+        let frame_proposals = Gauge::default();
+        context.register(
+            "frame_proposals",
+            "Number of block proposals in the last finalized frame",
+            frame_proposals.clone(),
+        );
+        let finalization_latency = Gauge::default();
+        context.register(
+            "finalization_latency",
+            "Time between the start and the finalization of the last frame (in milliseconds)",
+            finalization_latency.clone(),
+        );
+        let fork_count = Gauge::default();
+        context.register(
+            "fork_count",
+            "Number of competing tips in the fork choice tree",
+            fork_count.clone(),
+        );
+
+        // Rebuild the state by replaying the blocks minted before a restart
+        let (blocks, len) = context
+            .open(STORAGE_PARTITION, BLOCKS_BLOB)
+            .await
+            .expect("failed to open block log");
+        let log = blocks.read_at(vec![0; len as usize], 0).await.expect("failed to read block log");
+        let mut buf = log.as_ref();
+        let mut block_number = 0;
+        let mut block_hash = [0; 32].into();
+        let cfg = (RangeCfg::from(0..=usize::MAX), ());
+        while !buf.is_empty() {
+            // Stop at a block that was only partially written (it is overwritten by the next one)
+            let Ok(txs) = Vec::<Transaction>::read_cfg(&mut buf, &cfg) else {
+                warn!(block_number = block_number + 1, "dropping partially logged block");
+                break;
+            };
+            let result = execute_state_transition(&mut state, txs);
+            block_number += 1;
+            block_hash = MintedBlock::compute_hash(&block_hash, block_number, &result.included_txs);
+        }
+        let blocks_size = len - buf.len() as u64;
+        if block_number > 0 {
+            info!(block_number, %block_hash, "replayed minted blocks");
+        }
+
+        let frame_started = context.current();
+        let last_finalized_frame = state.fork_tree.finalized_frame();
         
-        Self {
+        let actor = Self {
             context,
 
-            buffer,
+            buffer: Some(buffer),
             buffer_mailbox,
+            queries: Some(queries),
             
             block_period: config.block_period,
+            align_to_epoch: config.align_to_epoch,
+            frame_recipients: config.frame_recipients,
             mempool,
 
             state,
-            block_number: 0,
-        }
+            blocks,
+            blocks_size,
+            block_number,
+            block_hash,
+            next_block: frame_started,
+            frame_started,
+            last_finalized_frame,
+
+            broadcast_failures,
+            broadcast_backlog,
+            frame_proposals,
+            finalization_latency,
+            fork_count,
+        };
+        (actor, Mailbox::new(sender))
     }
 
     pub fn start(
         mut self,
         tx_receiver: impl Receiver<PublicKey = PublicKey>,
         event_network: (
-            impl Receiver<PublicKey = PublicKey>,
             impl Sender<PublicKey = PublicKey>,
+            impl Receiver<PublicKey = PublicKey>,
         )
-    ) -> Handle<()> {
-        self.context.spawn_ref()(self.run(tx_receiver))
+    ) -> (Handle<()>, ShutdownHandle) {
+        let (sender, stop) = mpsc::channel(1);
+        let handle = self.context.spawn_ref()(self.run(tx_receiver, event_network, stop));
+        (handle, ShutdownHandle::new(sender))
     }
 
     async fn run(
         mut self,
         mut tx_receiver: impl Receiver<PublicKey = PublicKey>,
+        event_network: (
+            impl Sender<PublicKey = PublicKey>,
+            impl Receiver<PublicKey = PublicKey>,
+        ),
+        stop: mpsc::Receiver<()>,
     ) {
+        // Start event broadcast
+        let buffer = self.buffer.take().expect("actor already started");
+        buffer.start(event_network);
+        let mut queries = self.queries.take();
+        let mut stop = Some(stop);
+
+        // Schedule the first block (later ones are scheduled once the previous one is minted,
+        // so other messages don't delay them)
+        self.next_block = self.next_block_deadline();
+
         loop {
             select! {
+                // Keeps running if the shutdown handle is dropped without being used
+                _ = next_message(&mut stop) => {
+                    self.shutdown().await;
+                    return;
+                },
+
                 result = tx_receiver.recv() => {
                     match result {
-                        Ok((_, msg)) => {
+                        Ok((peer, msg)) => {
                             match Transaction::decode_cfg(msg, &()) {
-                                Ok(tx) => self.mempool.add(tx),
+                                Ok(tx) => {
+                                    debug!(sender = %tx.public_key, nonce = tx.nonce, "received transaction");
+                                    let encoded = tx.encode().to_vec();
+                                    let next_nonce = self.state.builders
+                                        .get(&tx.public_key)
+                                        .map(|builder| builder.nonce)
+                                        .unwrap_or(0);
+                                    if self.mempool.add_expecting(tx, next_nonce) == AddResult::Added {
+                                        // Relay to peers that may have missed the transaction
+                                        self.gossip(MessageEvent::TransactionGossip(encoded)).await;
+                                    }
+                                },
                                 Err(err) => {
-                                    todo!();
+                                    warn!(%peer, ?err, "failed to decode transaction");
                                     continue
                                 }
                             };
                         },
                         Err(err) => {
-                            todo!()
+                            warn!(?err, "transaction receiver closed");
+                            self.shutdown().await;
+                            return;
                         },
                     }
                 },
                 
-                _ = self.context.sleep(self.block_period) => {
-                    self.mint_block().await;
+                message = next_message(&mut queries) => {
+                    let response = self.handle_query(message.query);
+                    let _ = message.response.send(response);
+                },
+
+                _ = self.context.sleep_until(self.next_block) => {
+                    let result = self.mint_block().await;
+                    self.next_block = self.next_block_deadline();
+                    debug!(
+                        block_number = self.block_number,
+                        included = result.included_txs.len(),
+                        "processed minted block"
+                    );
                 }
             }
         }
     }
 
-    async fn mint_block(&mut self) {
+    fn next_block_deadline(&self) -> SystemTime {
+        let now = self.context.current();
+        if !self.align_to_epoch {
+            return now + self.block_period;
+        }
+
+        // Tick on the next multiple of the block period since the unix epoch, so the
+        // schedule doesn't depend on processing time or restarts
+        let period = self.block_period.as_nanos();
+        let elapsed = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+        let next = (elapsed / period + 1) * period;
+        UNIX_EPOCH + Duration::from_nanos(next as u64)
+    }
+
+    #[instrument(skip_all, fields(block_number = self.block_number + 1, tx_count))]
+    async fn mint_block(&mut self) -> StateTransitionResult {
         // Get all pending transaction from mempool and execute them
         let mut txs = Vec::<Transaction>::new();
-        while let Some(tx) = self.mempool.next() {
+        while let Some(tx) = self.mempool.next(self.block_number + 1) {
             txs.push(tx);
         }
+        Span::current().record("tx_count", txs.len());
+
+        // Log the transactions before executing them, so a restart replays the same block
+        let record = txs.encode().to_vec();
+        let record_size = record.len() as u64;
+        self.blocks
+            .write_at(record, self.blocks_size)
+            .await
+            .expect("failed to log minted block");
+        self.blocks_size += record_size;
+
         let result = execute_state_transition(&mut self.state, txs);
         self.block_number += 1;
+        self.block_hash = MintedBlock::compute_hash(
+            &self.block_hash,
+            self.block_number,
+            &result.included_txs,
+        );
+        info!(
+            block_hash = %self.block_hash,
+            invalid_count = result.invalid_txs.len(),
+            "minted block"
+        );
         
         // Signal new block and finalized frame
-        _ =self.buffer_mailbox.broadcast(
+        self.broadcast(
             Recipients::All,
-            MessageEvent::BlockMinted(self.block_number),
+            MessageEvent::BlockMinted(MintedBlock {
+                block_number: self.block_number,
+                block_hash: self.block_hash,
+            }),
         ).await;
         
-        for event in result.generated_events {
-            if let Event::FrameFinalized(frame) = event {
-                _ = self.buffer_mailbox.broadcast(
-                    Recipients::All,
-                    MessageEvent::FrameFinalized(frame),
-                ).await;
+        for event in &result.generated_events {
+            match event {
+                Event::BlockProposed(proposed) => {
+                    debug!(
+                        builder = %proposed.builder,
+                        height = proposed.height,
+                        block_hash = %proposed.block_hash,
+                        "block proposed"
+                    );
+                }
+                Event::FrameFinalized(frame) => {
+                    // Skip frames that were already broadcast
+                    if frame.frame_number <= self.last_finalized_frame {
+                        debug!(frame_number = frame.frame_number, "skipping stale finalized frame");
+                        continue;
+                    }
+                    self.last_finalized_frame = frame.frame_number;
+
+                    // Update metrics (the proposal count is reset with the frame)
+                    let now = self.context.current();
+                    let latency = now.duration_since(self.frame_started).unwrap_or_default();
+                    self.finalization_latency.set(latency.as_millis() as i64);
+                    self.frame_proposals.set(self.state.finalized_frame_proposal_count as i64);
+                    self.frame_started = now;
+
+                    info!(
+                        frame_number = frame.frame_number,
+                        chain_head = %frame.chain_head,
+                        "finalized frame"
+                    );
+                    self.broadcast(
+                        self.frame_recipients(),
+                        MessageEvent::FrameFinalized(frame.clone()),
+                    ).await;
+                }
+                Event::FinalizationStalled(frame_number) => {
+                    warn!(frame_number = *frame_number, "frame finalization stalled");
+                }
+                Event::StakeWithdrawn(withdrawn) => {
+                    info!(builder = %withdrawn.builder, amount = withdrawn.amount, "stake withdrawn");
+                }
             }
         }
 
+        // Update metrics
+        self.fork_count.set(self.state.fork_tree.fork_count() as i64);
+
         // Clear mempool
         for (public, next_nonce) in &result.processed_nonces {
+            debug!(%public, next_nonce, "retaining mempool transactions");
             self.mempool.retain(public, *next_nonce);
         }
+
+        result
+    }
+
+    /// Persist the minted blocks before exiting (broadcasts are awaited as they are made, so
+    /// none are left pending).
+    async fn shutdown(&mut self) {
+        if let Err(err) = self.blocks.sync().await {
+            warn!(?err, "failed to persist minted blocks");
+        }
+        info!(block_number = self.block_number, "oracle stopped");
+    }
+
+    fn handle_query(&self, query: Query) -> QueryResponse {
+        match query {
+            Query::FinalizedFramesSince(frame_number) => {
+                QueryResponse::FinalizedFrames(self.state.finalized_frames_since(frame_number))
+            }
+        }
+    }
+
+    fn frame_recipients(&self) -> Recipients<PublicKey> {
+        if self.frame_recipients.is_empty() {
+            Recipients::All
+        } else {
+            Recipients::Some(self.frame_recipients.clone())
+        }
+    }
+
+    /// Hand a best-effort event to the buffer without waiting for (or retrying) its delivery.
+    async fn gossip(&mut self, event: MessageEvent) {
+        let _ = self.buffer_mailbox.broadcast(Recipients::All, event).await;
+    }
+
+    async fn broadcast(&mut self, recipients: Recipients<PublicKey>, event: MessageEvent) -> bool {
+        // Don't wait between attempts while the buffer is saturated
+        let attempts = if self.broadcast_backlog.get() >= BROADCAST_SATURATION {
+            1
+        } else {
+            BROADCAST_ATTEMPTS
+        };
+        let delivered = broadcast_with_retries(
+            &mut self.buffer_mailbox,
+            &self.context,
+            recipients,
+            event,
+            attempts,
+        ).await;
+        if delivered {
+            if self.broadcast_backlog.get() >= BROADCAST_SATURATION {
+                info!("event broadcasts recovered");
+            }
+            self.broadcast_backlog.set(0);
+            return true;
+        }
+
+        // Give up on the event
+        self.broadcast_failures.inc();
+        let backlog = self.broadcast_backlog.inc() + 1;
+        warn!(attempts, backlog, "failed to broadcast event");
+        if backlog == BROADCAST_SATURATION {
+            warn!(backlog, "event broadcast buffer saturated");
+        }
+        false
+    }
+}
+
+/// Broadcast an event until it reaches at least one peer, making up to `attempts` attempts.
+async fn broadcast_with_retries<B>(
+    broadcaster: &mut B,
+    context: &impl Clock,
+    recipients: Recipients<PublicKey>,
+    event: MessageEvent,
+    attempts: usize,
+) -> bool
+where
+    B: Broadcaster<Recipients = Recipients<PublicKey>, Message = MessageEvent, Response = Vec<PublicKey>>,
+{
+    for attempt in 1..=attempts {
+        let result = broadcaster
+            .broadcast(recipients.clone(), event.clone())
+            .await
+            .await;
+        match result {
+            Ok(peers) if !peers.is_empty() => return true,
+            Ok(_) => warn!(attempt, "event broadcast reached no peers"),
+            Err(_) => warn!(attempt, "event broadcast was dropped"),
+        }
+
+        // Wait before trying again
+        if attempt < attempts {
+            context.sleep(BROADCAST_RETRY_DELAY).await;
+        }
+    }
+    false
+}
+
+/// Wait for the next message of the receiver, disabling it (waiting forever) once all its
+/// senders are dropped.
+async fn next_message<T>(receiver: &mut Option<mpsc::Receiver<T>>) -> T {
+    let Some(inner) = receiver.as_mut() else {
+        return future::pending().await;
+    };
+    if let Some(message) = inner.next().await {
+        return message;
+    }
+    *receiver = None;
+    future::pending().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commonware_cryptography::PrivateKeyExt;
+    use commonware_runtime::{deterministic, Runner};
+    use futures::channel::oneshot;
+
+    use crate::{
+        testkit::{client_key, default_config, sign_transaction, spawn_test_oracle, start_test_oracle},
+        types::{BlockProposal, Frame, Instruction, MintedBlock},
+    };
+    use tracing_test::traced_test;
+
+    /// A broadcaster failing a given number of times before reaching a peer.
+    #[derive(Clone)]
+    struct FlakyBroadcaster {
+        failures: usize,
+        attempts: usize,
+        peer: PublicKey,
+    }
+
+    impl Broadcaster for FlakyBroadcaster {
+        type Recipients = Recipients<PublicKey>;
+        type Message = MessageEvent;
+        type Response = Vec<PublicKey>;
+
+        async fn broadcast(
+            &mut self,
+            _: Recipients<PublicKey>,
+            _: MessageEvent,
+        ) -> oneshot::Receiver<Vec<PublicKey>> {
+            let (sender, receiver) = oneshot::channel();
+            let peers = if self.attempts < self.failures {
+                Vec::new()
+            } else {
+                vec![self.peer.clone()]
+            };
+            self.attempts += 1;
+            let _ = sender.send(peers);
+            receiver
+        }
+    }
+
+    fn minted_block(block_number: u64) -> MessageEvent {
+        MessageEvent::BlockMinted(MintedBlock {
+            block_number,
+            block_hash: [block_number as u8; 32].into(),
+        })
+    }
+
+    /// Run an oracle receiving a proposal, returning its first encoded events.
+    fn run_oracle(seed: u64) -> Vec<Vec<u8>> {
+        let executor = deterministic::Runner::seeded(seed);
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(1);
+            let mut config = default_config(PrivateKey::from_seed(0));
+            config.genesis_builders = vec![builder.public_key()];
+            config.rng_seed = Some(seed);
+            let mut oracle = spawn_test_oracle(context, config).await;
+            let tx = sign_transaction(&builder, 0, Instruction::ProposeBlock(BlockProposal {
+                block_height: 1,
+                parent_hash: [1; 32].into(),
+                block_hash: [2; 32].into(),
+            }));
+            assert!(oracle.tx_sender.submit(&tx).await);
+
+            let mut events = Vec::new();
+            while events.len() < 4 {
+                let event = oracle.events.next().await.expect("oracle stopped");
+                events.push(event.encode().to_vec());
+            }
+            events
+        })
+    }
+
+    /// Get the current value of an oracle metric (from the root context).
+    fn metric(context: &impl Metrics, name: &str) -> i64 {
+        let prefix = format!("oracle_{name} ");
+        context
+            .encode()
+            .lines()
+            .find_map(|line| line.strip_prefix(prefix.as_str()))
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_else(|| panic!("missing metric {name}"))
+    }
+
+    /// Run an oracle finalizing a frame in its first block, returning the frames received by
+    /// the client until the third block.
+    fn received_frames(frame_recipients: Vec<PublicKey>) -> Vec<Frame> {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(1);
+            let mut config = default_config(PrivateKey::from_seed(0));
+            config.genesis_builders = vec![builder.public_key()];
+            config.frame_recipients = frame_recipients;
+            let mut oracle = spawn_test_oracle(context, config).await;
+            let tx = sign_transaction(&builder, 0, Instruction::ProposeBlock(BlockProposal {
+                block_height: 1,
+                parent_hash: [1; 32].into(),
+                block_hash: [2; 32].into(),
+            }));
+            assert!(oracle.tx_sender.submit(&tx).await);
+
+            let mut frames = Vec::new();
+            loop {
+                match oracle.events.next().await {
+                    Some(MessageEvent::BlockMinted(block)) if block.block_number == 3 => break,
+                    Some(MessageEvent::FrameFinalized(frame)) => frames.push(frame),
+                    Some(_) => continue,
+                    None => panic!("oracle stopped"),
+                }
+            }
+
+            // The frame is finalized whoever receives it
+            let finalized = oracle.mailbox.finalized_frames_since(2).await.expect("oracle stopped");
+            assert_eq!(finalized.len(), 1);
+            frames
+        })
+    }
+
+    #[test]
+    fn test_broadcast_retries() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let peer = PrivateKey::from_seed(1).public_key();
+
+            // Succeed on the last attempt
+            let mut broadcaster = FlakyBroadcaster { failures: 2, attempts: 0, peer: peer.clone() };
+            assert!(broadcast_with_retries(
+                &mut broadcaster,
+                &context,
+                Recipients::All,
+                minted_block(1),
+                BROADCAST_ATTEMPTS,
+            ).await);
+            assert_eq!(broadcaster.attempts, 3);
+
+            // Give up once all attempts failed
+            let mut broadcaster = FlakyBroadcaster { failures: 3, attempts: 0, peer };
+            assert!(!broadcast_with_retries(
+                &mut broadcaster,
+                &context,
+                Recipients::All,
+                minted_block(2),
+                BROADCAST_ATTEMPTS,
+            ).await);
+            assert_eq!(broadcaster.attempts, 3);
+        });
+    }
+
+    #[test]
+    fn test_first_block_broadcast() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            // The buffer engine must be running for the first event to reach the client
+            let config = default_config(PrivateKey::from_seed(0));
+            let mut oracle = spawn_test_oracle(context, config).await;
+            let Some(MessageEvent::BlockMinted(block)) = oracle.events.next().await else {
+                panic!("expected a minted block");
+            };
+            assert_eq!(block.block_number, 1);
+        });
+    }
+
+    #[test]
+    fn test_block_minted_hash() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let config = default_config(PrivateKey::from_seed(0));
+            let mut oracle = spawn_test_oracle(context, config).await;
+
+            // Each block hash chains the previous one
+            let mut parent = [0; 32].into();
+            for block_number in 1..=2 {
+                let Some(MessageEvent::BlockMinted(block)) = oracle.events.next().await else {
+                    panic!("expected a minted block");
+                };
+                let block_hash = MintedBlock::compute_hash(&parent, block_number, &[]);
+                assert_eq!(block, MintedBlock { block_number, block_hash });
+                parent = block_hash;
+            }
+        });
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_block_minted_log() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let config = default_config(PrivateKey::from_seed(0));
+            let mut oracle = spawn_test_oracle(context, config).await;
+            assert!(matches!(oracle.events.next().await, Some(MessageEvent::BlockMinted(_))));
+        });
+        assert!(logs_contain("minted block"));
+        assert!(logs_contain("mint_block{block_number=1"));
+    }
+
+    #[test]
+    fn test_finalization_latency() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(1);
+            let mut config = default_config(PrivateKey::from_seed(0));
+            config.genesis_builders = vec![builder.public_key()];
+            let mut oracle = spawn_test_oracle(context.clone(), config).await;
+
+            // Finalize a frame in the first blocks
+            let tx = sign_transaction(&builder, 0, Instruction::ProposeBlock(BlockProposal {
+                block_height: 1,
+                parent_hash: [1; 32].into(),
+                block_hash: [2; 32].into(),
+            }));
+            assert!(oracle.tx_sender.submit(&tx).await);
+            loop {
+                match oracle.events.next().await {
+                    Some(MessageEvent::FrameFinalized(_)) => break,
+                    Some(_) => continue,
+                    None => panic!("oracle stopped"),
+                }
+            }
+
+            // The frame started with the oracle, at least a block period earlier
+            let metrics = context.encode();
+            let latency = metrics
+                .lines()
+                .find_map(|line| line.strip_prefix("oracle_finalization_latency "))
+                .and_then(|value| value.parse::<i64>().ok())
+                .expect("missing finalization latency");
+            assert!(latency >= 1_000);
+        });
+    }
+
+    #[test]
+    fn test_same_seed() {
+        // Runs with the same seed mint the same blocks and frames
+        let events = run_oracle(7);
+        assert!(events.iter().any(|event| event[1] == 1));
+        assert_eq!(run_oracle(7), events);
+    }
+
+    #[test]
+    fn test_finalized_frames_since() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(1);
+            let mut config = default_config(PrivateKey::from_seed(0));
+            config.genesis_builders = vec![builder.public_key()];
+            let mut oracle = spawn_test_oracle(context, config).await;
+
+            // Finalize frames 2 to 4, each with a single block
+            for n in 2..=4u8 {
+                let tx = sign_transaction(&builder, n as u64 - 2, Instruction::ProposeBlock(BlockProposal {
+                    block_height: n as u64 - 1,
+                    parent_hash: [n - 1; 32].into(),
+                    block_hash: [n; 32].into(),
+                }));
+                assert!(oracle.tx_sender.submit(&tx).await);
+                loop {
+                    match oracle.events.next().await {
+                        Some(MessageEvent::FrameFinalized(frame)) if frame.frame_number == n as u64 => break,
+                        Some(_) => continue,
+                        None => panic!("oracle stopped"),
+                    }
+                }
+            }
+
+            // Get the frames from the oldest to the latest
+            let frames = oracle.mailbox.finalized_frames_since(2).await.expect("oracle stopped");
+            let expected = (2..=4u8)
+                .map(|n| Frame {
+                    frame_number: n as u64,
+                    chain_head: [n; 32].into(),
+                    finalized_blocks: vec![[n; 32].into()],
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(frames, expected);
+
+            // Only get the frames since the requested one
+            let frames = oracle.mailbox.finalized_frames_since(4).await.expect("oracle stopped");
+            assert_eq!(frames, expected[2..]);
+        });
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(1);
+            let config = || {
+                let mut config = default_config(PrivateKey::from_seed(0));
+                config.genesis_builders = vec![builder.public_key()];
+                config
+            };
+            let propose = |nonce, n: u8| {
+                sign_transaction(&builder, nonce, Instruction::ProposeBlock(BlockProposal {
+                    block_height: n as u64 - 1,
+                    parent_hash: [n - 1; 32].into(),
+                    block_hash: [n; 32].into(),
+                }))
+            };
+
+            // Finalize frame 2 in the first block, then mint a second one
+            let mut oracle = spawn_test_oracle(context.with_label("first"), config()).await;
+            assert!(oracle.tx_sender.submit(&propose(0, 2)).await);
+            let last = loop {
+                match oracle.events.next().await {
+                    Some(MessageEvent::BlockMinted(block)) if block.block_number == 2 => break block,
+                    Some(_) => continue,
+                    None => panic!("oracle stopped"),
+                }
+            };
+
+            // The task exits once signaled
+            oracle.shutdown.shutdown().await;
+            oracle.handle.await.expect("oracle failed");
+
+            // A restarted oracle rebuilds the same state
+            let mut oracle = spawn_test_oracle(context.with_label("restarted"), config()).await;
+            let frames = oracle.mailbox.finalized_frames_since(2).await.expect("oracle stopped");
+            assert_eq!(frames.len(), 1);
+
+            // It resumes after the last minted block, accepting the next proposal of the builder
+            let tx = propose(1, 3);
+            assert!(oracle.tx_sender.submit(&tx).await);
+            let block = loop {
+                match oracle.events.next().await {
+                    Some(MessageEvent::BlockMinted(block)) => break block,
+                    Some(_) => continue,
+                    None => panic!("oracle stopped"),
+                }
+            };
+            assert_eq!(block, MintedBlock {
+                block_number: 3,
+                block_hash: MintedBlock::compute_hash(&last.block_hash, 3, &[tx]),
+            });
+            let Some(MessageEvent::FrameFinalized(frame)) = oracle.events.next().await else {
+                panic!("expected a finalized frame");
+            };
+            assert_eq!(frame.frame_number, 3);
+        });
+    }
+
+    #[test]
+    fn test_shutdown_handle_dropped() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let config = default_config(PrivateKey::from_seed(0));
+            let mut oracle = spawn_test_oracle(context, config).await;
+
+            // Keep minting without a shutdown handle
+            drop(oracle.shutdown);
+            for block_number in 1..=3 {
+                let Some(MessageEvent::BlockMinted(block)) = oracle.events.next().await else {
+                    panic!("expected a minted block");
+                };
+                assert_eq!(block.block_number, block_number);
+            }
+        });
+    }
+
+    #[test]
+    fn test_broadcast_saturation() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut config = default_config(PrivateKey::from_seed(0));
+            config.block_period = Duration::from_millis(10);
+            let mut oracle = spawn_test_oracle(context.clone(), config).await;
+
+            // Count the consecutive failures while no event can be delivered
+            oracle.disconnect_events().await;
+            context.sleep(Duration::from_secs(5)).await;
+            let backlog = metric(&context, "broadcast_backlog");
+            assert_eq!(metric(&context, "broadcast_failures_total"), backlog);
+
+            // Once saturated, events are no longer retried and blocks keep their pace (retrying
+            // every event would fail fewer than twice the saturation in that time)
+            assert!(backlog > 2 * BROADCAST_SATURATION);
+
+            // Reset the backlog once an event is delivered again
+            oracle.connect_events().await;
+            assert!(matches!(oracle.events.next().await, Some(MessageEvent::BlockMinted(_))));
+            assert_eq!(metric(&context, "broadcast_backlog"), 0);
+            assert!(metric(&context, "broadcast_failures_total") >= backlog);
+        });
+    }
+
+    #[test]
+    fn test_frame_recipients() {
+        // Send frames to all peers by default
+        assert_eq!(received_frames(Vec::new()).len(), 1);
+
+        // Only send them to the configured peers otherwise
+        assert_eq!(received_frames(vec![client_key()]).len(), 1);
+        assert!(received_frames(vec![PrivateKey::from_seed(2).public_key()]).is_empty());
+    }
+
+    #[test]
+    fn test_finalized_frame_dedup() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(1);
+            let mut config = default_config(PrivateKey::from_seed(0));
+            config.genesis_builders = vec![builder.public_key()];
+            let oracle_key = config.event_signer.public_key();
+
+            // The oracle already broadcast frame 2 (the genesis frame is 1), so finalizing it
+            // again must not reach the client
+            let (mut actor, mailbox) = Actor::new(context.with_label("oracle"), config).await;
+            actor.last_finalized_frame = 2;
+            let mut oracle = start_test_oracle(context, oracle_key, actor, mailbox).await;
+
+            // Finalize frames 2 and 3 in the first block
+            for n in 2..=3u8 {
+                let tx = sign_transaction(&builder, n as u64 - 2, Instruction::ProposeBlock(BlockProposal {
+                    block_height: n as u64 - 1,
+                    parent_hash: [n - 1; 32].into(),
+                    block_hash: [n; 32].into(),
+                }));
+                assert!(oracle.tx_sender.submit(&tx).await);
+            }
+
+            // Only the new frame is broadcast
+            let mut frames = Vec::new();
+            loop {
+                match oracle.events.next().await {
+                    Some(MessageEvent::BlockMinted(block)) if block.block_number == 2 => break,
+                    Some(MessageEvent::FrameFinalized(frame)) => frames.push(frame.frame_number),
+                    Some(_) => continue,
+                    None => panic!("oracle stopped"),
+                }
+            }
+            assert_eq!(frames, vec![3]);
+
+            // Both frames were finalized
+            let finalized = oracle.mailbox.finalized_frames_since(2).await.expect("oracle stopped");
+            assert_eq!(finalized.len(), 2);
+        });
+    }
+
+    #[test]
+    fn test_block_ticks() {
+        for align_to_epoch in [false, true] {
+            let executor = deterministic::Runner::default();
+            executor.start(|context| async move {
+                // Start off the block period boundaries
+                let period = Duration::from_millis(500);
+                context.sleep(Duration::from_millis(123)).await;
+                let started = context.current();
+                let mut config = default_config(PrivateKey::from_seed(0));
+                config.block_period = period;
+                config.align_to_epoch = align_to_epoch;
+                let oracle = spawn_test_oracle(context.clone(), config).await;
+                let (mut tx_sender, mut events) = (oracle.tx_sender, oracle.events);
+
+                // Keep sending transactions faster than blocks are minted
+                let _traffic = context.with_label("traffic").spawn(move |context| async move {
+                    let tx = sign_transaction(&PrivateKey::from_seed(1), 0, Instruction::DeactivateBuilder);
+                    loop {
+                        tx_sender.submit(&tx).await;
+                        context.sleep(Duration::from_millis(100)).await;
+                    }
+                });
+
+                // Blocks are minted every period from the start (or from the first period
+                // boundary when aligned), whatever the traffic
+                let first_block = if align_to_epoch {
+                    let elapsed = started.duration_since(UNIX_EPOCH).unwrap().as_nanos();
+                    let next = (elapsed / period.as_nanos() + 1) * period.as_nanos();
+                    UNIX_EPOCH + Duration::from_nanos(next as u64)
+                } else {
+                    started + period
+                };
+                for block_number in 1..=3u32 {
+                    loop {
+                        match events.next().await {
+                            Some(MessageEvent::BlockMinted(block)) => {
+                                assert_eq!(block.block_number, block_number as u64);
+                                break;
+                            }
+                            Some(_) => continue,
+                            None => panic!("oracle stopped"),
+                        }
+                    }
+                    let expected = first_block + period * (block_number - 1);
+                    let delay = context.current().duration_since(expected).expect("block minted early");
+                    assert!(delay < Duration::from_millis(10));
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_frame_metrics() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(1);
+            let mut config = default_config(PrivateKey::from_seed(0));
+            config.genesis_builders = vec![builder.public_key()];
+            config.finalization_trigger = FinalizationTrigger::ProposalCount(2);
+            config.frame_retention = Some(0);
+            let mut oracle = spawn_test_oracle(context.clone(), config).await;
+            let propose = |nonce, block_hash: u8| {
+                sign_transaction(&builder, nonce, Instruction::ProposeBlock(BlockProposal {
+                    block_height: 1,
+                    parent_hash: [1; 32].into(),
+                    block_hash: [block_hash; 32].into(),
+                }))
+            };
+
+            // Two competing blocks tie, so the first block finalizes no frame
+            for tx in [propose(0, 2), propose(1, 3)] {
+                assert!(oracle.tx_sender.submit(&tx).await);
+            }
+            loop {
+                match oracle.events.next().await {
+                    Some(MessageEvent::BlockMinted(_)) => break,
+                    Some(_) => continue,
+                    None => panic!("oracle stopped"),
+                }
+            }
+            assert_eq!(metric(&context, "fork_count"), 1);
+            assert_eq!(metric(&context, "frame_proposals"), 0);
+
+            // Another proposal breaks the tie, finalizing a frame of three proposals (and
+            // pruning the losing fork)
+            assert!(oracle.tx_sender.submit(&propose(2, 2)).await);
+            loop {
+                match oracle.events.next().await {
+                    Some(MessageEvent::FrameFinalized(_)) => break,
+                    Some(_) => continue,
+                    None => panic!("oracle stopped"),
+                }
+            }
+            assert_eq!(metric(&context, "frame_proposals"), 3);
+            assert_eq!(metric(&context, "fork_count"), 0);
+        });
+    }
+
+    #[test]
+    fn test_included_transactions() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(1);
+            let mut config = default_config(PrivateKey::from_seed(0));
+            config.genesis_builders = vec![builder.public_key()];
+            let mut oracle = spawn_test_oracle(context, config).await;
+            let propose = |signer: &PrivateKey, nonce, parent_hash: u8| {
+                sign_transaction(signer, nonce, Instruction::ProposeBlock(BlockProposal {
+                    block_height: 1,
+                    parent_hash: [parent_hash; 32].into(),
+                    block_hash: [2; 32].into(),
+                }))
+            };
+
+            // Submit a valid proposal, one from an unknown builder and one on an unknown parent
+            let valid = propose(&builder, 0, 1);
+            let txs = [valid.clone(), propose(&PrivateKey::from_seed(2), 0, 1), propose(&builder, 1, 9)];
+            for tx in &txs {
+                assert!(oracle.tx_sender.submit(tx).await);
+            }
+
+            // The block hash only commits to the included transaction
+            let block = loop {
+                match oracle.events.next().await {
+                    Some(MessageEvent::BlockMinted(block)) => break block,
+                    Some(_) => continue,
+                    None => panic!("oracle stopped"),
+                }
+            };
+            let parent = [0; 32].into();
+            assert_eq!(block.block_hash, MintedBlock::compute_hash(&parent, 1, &[valid]));
+            assert_ne!(block.block_hash, MintedBlock::compute_hash(&parent, 1, &txs));
+        });
     }
 }
\ No newline at end of file
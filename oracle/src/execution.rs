@@ -1,35 +1,165 @@
-use std::collections::{HashMap, BTreeMap};
+use std::collections::{HashMap, HashSet, BTreeMap};
 
 use commonware_cryptography::{
     sha256::Digest,
     ed25519::PublicKey
 };
+use commonware_runtime::Metrics;
 
-use fcn_common::fork_choice_tree::ForkChoiceTree;
+use fcn_common::fork_choice_tree::{ForkChoiceTree, ForkChoiceTreeConfig, ScoreDecay};
 
-use crate::types::{BuilderAccount, Event, Frame, Instruction, Transaction};
+use crate::types::{
+    BuilderAccount, Event, Frame, Instruction, ProposedBlock, Transaction, WithdrawnStake,
+    MAX_FRAME_BLOCKS,
+};
+
+pub enum FinalizationTrigger {
+    /// Finalize once the frame received the given number of block proposals.
+    ProposalCount(u64),
+    /// Finalize once `numerator / denominator` of the registered builders proposed a block
+    /// in the frame.
+    BuilderQuorum { numerator: u64, denominator: u64 },
+}
+
+pub struct Config {
+    pub genesis_block_hash: Digest,
+    pub genesis_parent: Digest,
+    pub max_fork_depth: Option<u64>,
+    pub score_decay: Option<ScoreDecay>,
+    pub hysteresis: u64,
+    /// Number of frames behind the finalized one kept in the fork tree (all if unset).
+    pub frame_retention: Option<u64>,
+    /// Builders registered (with no stake) when the network starts.
+    pub genesis_builders: Vec<PublicKey>,
+
+    pub finalization_trigger: FinalizationTrigger,
+    pub max_finalization_attempts: u64,
+    pub max_proposals_per_builder_per_frame: u64,
+    /// Maximum stake of a single builder (bounding the weight of its proposals).
+    pub max_builder_stake: u64,
+}
 
 pub struct State {
     pub builders: HashMap<PublicKey, BuilderAccount>,
     pub fork_tree: ForkChoiceTree,
+    pub frame_retention: Option<u64>,
+    /// Heights of the first and last blocks finalized by each frame.
+    pub frame_ranges: BTreeMap<u64, (u64, u64)>,
     
-    pub finalize_frame_block_proposal_min: u64,
+    pub finalization_trigger: FinalizationTrigger,
     pub frame_block_proposal_count: u64,
+    pub frame_proposers: HashSet<PublicKey>,
+    /// Number of block proposals in the last finalized frame.
+    pub finalized_frame_proposal_count: u64,
+
+    pub max_finalization_attempts: u64,
+    pub finalization_attempts: u64,
+
+    pub max_proposals_per_builder_per_frame: u64,
+    pub max_builder_stake: u64,
 }
 
 impl State {
-    pub fn new(genesis_block_hash: Digest, finalize_frame_block_proposal_min: u64) -> Self {
+    pub fn new(context: impl Metrics, config: Config) -> Self {
+        if let FinalizationTrigger::BuilderQuorum { numerator, denominator } = config.finalization_trigger {
+            assert!(numerator > 0, "builder quorum must be positive");
+            assert!(numerator <= denominator, "builder quorum must not exceed every builder");
+        }
+
+        let builders = config.genesis_builders
+            .into_iter()
+            .map(|public| (public, BuilderAccount::default()))
+            .collect();
+
+        // A frame finalizes at most as many blocks as a proposal may be ahead of the finalized
+        // head, so bounding the depth keeps every frame decodable
+        let max_fork_depth = config.max_fork_depth
+            .map_or(MAX_FRAME_BLOCKS as u64, |depth| depth.min(MAX_FRAME_BLOCKS as u64));
+
         Self {
-            builders: HashMap::new(),
-            fork_tree: ForkChoiceTree::new(genesis_block_hash),
+            builders,
+            fork_tree: ForkChoiceTree::new(context.with_label("fork_tree"), ForkChoiceTreeConfig {
+                genesis_block_hash: config.genesis_block_hash,
+                genesis_parent: config.genesis_parent,
+                max_fork_depth: Some(max_fork_depth),
+                score_decay: config.score_decay,
+                hysteresis: config.hysteresis,
+            }),
+            frame_retention: config.frame_retention,
+            frame_ranges: BTreeMap::new(),
 
-            finalize_frame_block_proposal_min,
+            finalization_trigger: config.finalization_trigger,
             frame_block_proposal_count: 0,
+            frame_proposers: HashSet::new(),
+            finalized_frame_proposal_count: 0,
+
+            max_finalization_attempts: config.max_finalization_attempts,
+            finalization_attempts: 0,
+
+            max_proposals_per_builder_per_frame: config.max_proposals_per_builder_per_frame,
+            max_builder_stake: config.max_builder_stake,
+        }
+    }
+
+    pub fn should_finalize(&self) -> bool {
+        match self.finalization_trigger {
+            FinalizationTrigger::ProposalCount(min) => self.frame_block_proposal_count >= min,
+            FinalizationTrigger::BuilderQuorum { numerator, denominator } => {
+                // Compare in u128 so large quorum terms can't overflow
+                !self.frame_proposers.is_empty()
+                    && self.frame_proposers.len() as u128 * denominator as u128
+                        >= self.active_builders() as u128 * numerator as u128
+            }
+        }
+    }
+
+    fn active_builders(&self) -> u64 {
+        self.builders.values().filter(|builder| builder.active).count() as u64
+    }
+
+    fn reset_frame_proposals(&mut self) {
+        self.frame_block_proposal_count = 0;
+        self.frame_proposers.clear();
+        for builder in self.builders.values_mut() {
+            builder.proposals_in_frame = 0;
+        }
+    }
+
+    /// Check if no active builder can propose another block in the frame.
+    fn all_builders_capped(&self) -> bool {
+        self.builders.values()
+            .filter(|builder| builder.active)
+            .all(|builder| builder.proposals_in_frame >= self.max_proposals_per_builder_per_frame)
+    }
+
+    pub fn finalized_frame(&self) -> Frame {
+        Frame {
+            frame_number: self.fork_tree.finalized_frame(),
+            chain_head: self.fork_tree.finalized_head(),
+            finalized_blocks: self.fork_tree.finalized_blocks().to_vec(),
         }
     }
+
+    /// Get the heights of the first and last blocks finalized by the frame (if any).
+    pub fn frame_range(&self, frame_number: u64) -> Option<(u64, u64)> {
+        self.frame_ranges.get(&frame_number).copied()
+    }
+
+    /// Frames finalized since the given frame number (included), from the oldest to the latest.
+    pub fn finalized_frames_since(&self, frame_number: u64) -> Vec<Frame> {
+        self.fork_tree
+            .finalized_chain(frame_number)
+            .map(|(frame_number, chain_head, finalized_blocks)| Frame {
+                frame_number,
+                chain_head,
+                finalized_blocks,
+            })
+            .collect()
+    }
 }
 
 pub struct StateTransitionResult {
+    pub included_txs: Vec<Transaction>,
     pub processed_nonces: BTreeMap<PublicKey, u64>,
     pub invalid_txs: Vec<Transaction>,
     pub generated_events: Vec<Event>,
@@ -55,7 +185,7 @@ pub fn execute_state_transition(
         };
 
         // Execute transaction
-        if let Some(events) = apply_transaction(state, &tx) {
+        if let Some(events) = apply_transaction(state, &sender, &tx) {
             generated_events.extend(events);
         } else {
             invalid_txs.push(tx);
@@ -68,6 +198,7 @@ pub fn execute_state_transition(
     }
 
     StateTransitionResult { 
+        included_txs: valid_txs,
         processed_nonces,
         invalid_txs,
         generated_events,
@@ -89,8 +220,8 @@ fn prepare_sender_account(state: &mut State, tx: &Transaction) -> Option<Builder
         return None;
     }
 
-    // Increment nonce
-    account.nonce += 1;
+    // Increment nonce (an account at the last nonce can't send transactions anymore)
+    account.nonce = account.nonce.checked_add(1)?;
     state.builders.insert(tx.public_key.clone(),account.clone());
 
     Some(account)
@@ -98,35 +229,553 @@ fn prepare_sender_account(state: &mut State, tx: &Transaction) -> Option<Builder
 
 fn apply_transaction(
     state: &mut State,
+    sender: &BuilderAccount,
     tx: &Transaction
 ) -> Option<Vec<Event>> {
     let mut events = Vec::<Event>::new();
     
     match &tx.instruction {
         Instruction::ProposeBlock(proposal) => {
-            if let Ok(()) = state.fork_tree.propose_block(proposal.block_height, proposal.parent_hash, proposal.block_hash) {
+            // Reject proposals of deactivated builders
+            if !sender.active {
+                return None
+            }
+
+            // Limit proposals of a single builder within the frame
+            if sender.proposals_in_frame >= state.max_proposals_per_builder_per_frame {
+                return None
+            }
+
+            if let Ok(()) = state.fork_tree.propose_block(
+                proposal.block_height,
+                proposal.parent_hash,
+                proposal.block_hash,
+                sender.proposal_weight(),
+            ) {
                 state.frame_block_proposal_count += 1;
+                state.frame_proposers.insert(tx.public_key.clone());
+
+                let mut builder = sender.clone();
+                builder.proposals_in_frame += 1;
+                state.builders.insert(tx.public_key.clone(), builder);
+
+                events.push(Event::BlockProposed(ProposedBlock {
+                    builder: tx.public_key.clone(),
+                    height: proposal.block_height,
+                    block_hash: proposal.block_hash,
+                }));
             } else {
                 return None
             }
         }
+        Instruction::DelegateStake(delegation) => {
+            // Stake of deactivated builders could never be released
+            if !sender.active {
+                return None
+            }
+
+            // Increase builder stake (up to the maximum)
+            let mut builder = sender.clone();
+            builder.stake = builder.stake
+                .checked_add(delegation.amount)
+                .filter(|stake| *stake <= state.max_builder_stake)?;
+            state.builders.insert(tx.public_key.clone(), builder);
+            return Some(events);
+        }
+        Instruction::DeactivateBuilder => {
+            if !sender.active {
+                return None
+            }
+
+            // Keep the account (and its nonce) to prevent replays
+            let mut builder = sender.clone();
+            builder.active = false;
+            builder.withdrawable_stake = builder.withdrawable_stake.checked_add(builder.stake)?;
+            builder.stake = 0;
+            state.builders.insert(tx.public_key.clone(), builder);
+            return Some(events);
+        }
+        Instruction::WithdrawStake => {
+            if sender.withdrawable_stake == 0 {
+                return None
+            }
+
+            // Transfer the released stake out of the builder account
+            let mut builder = sender.clone();
+            let amount = std::mem::take(&mut builder.withdrawable_stake);
+            state.builders.insert(tx.public_key.clone(), builder);
+            events.push(Event::StakeWithdrawn(WithdrawnStake {
+                builder: tx.public_key.clone(),
+                amount,
+            }));
+            return Some(events);
+        }
     }
 
-    // Finalize frame max number of ProposeBlock txs has been received
-    if state.frame_block_proposal_count >= state.finalize_frame_block_proposal_min {
+    // Finalize frame once enough ProposeBlock txs have been received
+    if state.should_finalize() {
         match state.fork_tree.finalize_block_frame() {
-            Ok((frame_number, chain_head)) => {
+            Ok(finalization) => {
+                // Record the heights covered by the frame
+                let first = finalization.blocks.first().and_then(|hash| state.fork_tree.height(*hash));
+                let last = state.fork_tree.height(finalization.new_head);
+                if let (Some(first), Some(last)) = (first, last) {
+                    state.frame_ranges.insert(finalization.frame_number, (first, last));
+                }
+
+                if let Some(retention) = state.frame_retention {
+                    state.fork_tree.prune_below_frame(finalization.frame_number.saturating_sub(retention));
+                }
+
                 events.push(Event::FrameFinalized(Frame{
-                    frame_number: frame_number,
-                    chain_head: chain_head,
+                    frame_number: finalization.frame_number,
+                    chain_head: finalization.new_head,
+                    finalized_blocks: finalization.blocks,
                 }));
-                state.frame_block_proposal_count = 0;
+                state.finalized_frame_proposal_count = state.frame_block_proposal_count;
+                state.reset_frame_proposals();
+                state.finalization_attempts = 0;
             },
-            Err(err) => {
-                todo!()
+            Err(_) => {
+                // Stop retrying on every proposal once the frame is stuck (or once no more
+                // proposals can be accepted to break the tie)
+                state.finalization_attempts += 1;
+                if state.finalization_attempts >= state.max_finalization_attempts
+                    || state.all_builders_capped()
+                {
+                    events.push(Event::FinalizationStalled(state.fork_tree.finalized_frame() + 1));
+                    state.reset_frame_proposals();
+                    state.finalization_attempts = 0;
+                }
             },
         }
     }
 
     Some(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commonware_cryptography::{ed25519::PrivateKey, PrivateKeyExt, Signer};
+    use commonware_runtime::{deterministic, Runner};
+
+    use crate::{
+        testkit::sign_transaction,
+        types::{BlockProposal, DelegateStake},
+    };
+
+    fn hash(n: u8) -> Digest {
+        [n; 32].into()
+    }
+
+    /// A config never finalizing frames, with the given genesis builders.
+    fn config(genesis_builders: Vec<PublicKey>) -> Config {
+        Config {
+            genesis_block_hash: hash(1),
+            genesis_parent: hash(0),
+            max_fork_depth: None,
+            score_decay: None,
+            hysteresis: 0,
+            frame_retention: None,
+            genesis_builders,
+
+            finalization_trigger: FinalizationTrigger::ProposalCount(u64::MAX),
+            max_finalization_attempts: 3,
+            max_proposals_per_builder_per_frame: u64::MAX,
+            max_builder_stake: u64::MAX,
+        }
+    }
+
+    fn propose(signer: &PrivateKey, nonce: u64, block_height: u64, parent: u8, block: u8) -> Transaction {
+        sign_transaction(signer, nonce, Instruction::ProposeBlock(BlockProposal {
+            block_height,
+            parent_hash: hash(parent),
+            block_hash: hash(block),
+        }))
+    }
+
+    fn delegate(signer: &PrivateKey, nonce: u64, amount: u64) -> Transaction {
+        sign_transaction(signer, nonce, Instruction::DelegateStake(DelegateStake { amount }))
+    }
+
+    #[test]
+    fn test_delegate_stake() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(0);
+            let mut config = config(vec![builder.public_key()]);
+            config.max_builder_stake = 10;
+            let mut state = State::new(context, config);
+
+            // Delegate stake (up to the maximum)
+            let result = execute_state_transition(&mut state, vec![
+                delegate(&builder, 0, 4),
+                delegate(&builder, 1, 7),
+            ]);
+            assert_eq!(result.included_txs.len(), 1);
+            assert_eq!(result.invalid_txs.len(), 1);
+            assert_eq!(state.builders[&builder.public_key()].stake, 4);
+
+            // Proposals weigh the stake of the builder
+            let result = execute_state_transition(&mut state, vec![propose(&builder, 2, 1, 1, 2)]);
+            assert_eq!(result.included_txs.len(), 1);
+            assert_eq!(state.fork_tree.score(hash(2)), Some(5));
+
+            // Deactivated builders can't delegate anymore
+            let result = execute_state_transition(&mut state, vec![
+                sign_transaction(&builder, 3, Instruction::DeactivateBuilder),
+                delegate(&builder, 4, 1),
+            ]);
+            assert_eq!(result.included_txs.len(), 1);
+            assert_eq!(result.invalid_txs.len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_finalization_stall() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builders = (0..3).map(PrivateKey::from_seed).collect::<Vec<_>>();
+            let mut config = config(builders.iter().map(|builder| builder.public_key()).collect());
+            config.finalization_trigger = FinalizationTrigger::ProposalCount(2);
+            config.max_finalization_attempts = 2;
+            let mut state = State::new(context, config);
+
+            // Every builder proposes a competing block, so the fork stays tied
+            let result = execute_state_transition(&mut state, vec![
+                propose(&builders[0], 0, 1, 1, 2),
+                propose(&builders[1], 0, 1, 1, 3),
+            ]);
+            assert!(result.generated_events.iter().all(|event| matches!(event, Event::BlockProposed(_))));
+            assert_eq!(state.finalization_attempts, 1);
+            assert_eq!(state.frame_block_proposal_count, 2);
+
+            // Give up on the frame after the last attempt
+            let result = execute_state_transition(&mut state, vec![propose(&builders[2], 0, 1, 1, 4)]);
+            assert!(result.generated_events.contains(&Event::FinalizationStalled(2)));
+            assert_eq!(state.finalization_attempts, 0);
+            assert_eq!(state.frame_block_proposal_count, 0);
+            assert_eq!(state.fork_tree.finalized_frame(), 1);
+        });
+    }
+
+    #[test]
+    fn test_proposal_cap() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let (alice, bob) = (PrivateKey::from_seed(0), PrivateKey::from_seed(1));
+            let mut config = config(vec![alice.public_key(), bob.public_key()]);
+            config.finalization_trigger = FinalizationTrigger::ProposalCount(3);
+            config.max_proposals_per_builder_per_frame = 2;
+            let mut state = State::new(context, config);
+
+            // Reject proposals above the cap within the frame
+            let result = execute_state_transition(&mut state, vec![
+                propose(&alice, 0, 1, 1, 2),
+                propose(&alice, 1, 2, 2, 3),
+                propose(&alice, 2, 3, 3, 4),
+            ]);
+            assert_eq!(result.included_txs.len(), 2);
+            assert_eq!(result.invalid_txs.len(), 1);
+            assert_eq!(state.builders[&alice.public_key()].proposals_in_frame, 2);
+
+            // Accept them again once the frame is finalized
+            let result = execute_state_transition(&mut state, vec![propose(&bob, 0, 3, 3, 4)]);
+            assert!(matches!(result.generated_events.last(), Some(Event::FrameFinalized(_))));
+            assert_eq!(state.builders[&alice.public_key()].proposals_in_frame, 0);
+            let result = execute_state_transition(&mut state, vec![propose(&alice, 3, 4, 4, 5)]);
+            assert_eq!(result.included_txs.len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_proposal_cap_stall() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let (alice, bob) = (PrivateKey::from_seed(0), PrivateKey::from_seed(1));
+            let mut config = config(vec![alice.public_key(), bob.public_key()]);
+            config.finalization_trigger = FinalizationTrigger::ProposalCount(2);
+            config.max_finalization_attempts = 10;
+            config.max_proposals_per_builder_per_frame = 1;
+            let mut state = State::new(context, config);
+
+            // Once every builder is capped, the tie can't be broken and the frame stalls
+            let result = execute_state_transition(&mut state, vec![
+                propose(&alice, 0, 1, 1, 2),
+                propose(&bob, 0, 1, 1, 3),
+            ]);
+            assert!(result.generated_events.contains(&Event::FinalizationStalled(2)));
+
+            // Caps are reset, so builders can break the tie
+            let result = execute_state_transition(&mut state, vec![propose(&alice, 1, 1, 1, 2)]);
+            assert_eq!(result.included_txs.len(), 1);
+            assert_eq!(state.fork_tree.score(hash(2)), Some(2));
+        });
+    }
+
+    #[test]
+    fn test_included_transactions() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(0);
+            let unknown = PrivateKey::from_seed(1);
+            let mut state = State::new(context, config(vec![builder.public_key()]));
+
+            // Only valid transactions are included (in order)
+            let valid = vec![propose(&builder, 0, 1, 1, 2), propose(&builder, 1, 2, 2, 3)];
+            let invalid = vec![
+                propose(&unknown, 0, 1, 1, 4),
+                propose(&builder, 5, 2, 2, 5),
+                propose(&builder, 2, 1, 9, 6),
+            ];
+            let result = execute_state_transition(&mut state, vec![
+                valid[0].clone(),
+                invalid[0].clone(),
+                invalid[1].clone(),
+                valid[1].clone(),
+                invalid[2].clone(),
+            ]);
+            assert_eq!(result.included_txs, valid);
+            assert_eq!(result.invalid_txs, invalid);
+        });
+    }
+
+    #[test]
+    fn test_builder_quorum() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builders = (0..3).map(PrivateKey::from_seed).collect::<Vec<_>>();
+            let mut config = config(builders.iter().map(|builder| builder.public_key()).collect());
+            config.finalization_trigger = FinalizationTrigger::BuilderQuorum { numerator: 2, denominator: 3 };
+            let mut state = State::new(context, config);
+
+            // Many proposals of a single builder don't reach the quorum
+            let result = execute_state_transition(&mut state, vec![
+                propose(&builders[0], 0, 1, 1, 2),
+                propose(&builders[0], 1, 2, 2, 3),
+                propose(&builders[0], 2, 3, 3, 4),
+            ]);
+            assert_eq!(result.included_txs.len(), 3);
+            assert!(!result.generated_events.iter().any(|event| matches!(event, Event::FrameFinalized(_))));
+            assert_eq!(state.frame_proposers.len(), 1);
+
+            // A second distinct builder does
+            let result = execute_state_transition(&mut state, vec![propose(&builders[1], 0, 3, 3, 4)]);
+            assert!(matches!(result.generated_events.last(), Some(Event::FrameFinalized(_))));
+            assert_eq!(state.fork_tree.finalized_head(), hash(4));
+            assert!(state.frame_proposers.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_finalized_blocks() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(0);
+            let mut config = config(vec![builder.public_key()]);
+            config.finalization_trigger = FinalizationTrigger::ProposalCount(2);
+            let mut state = State::new(context, config);
+
+            // Report every block finalized by the frame
+            let result = execute_state_transition(&mut state, vec![
+                propose(&builder, 0, 1, 1, 2),
+                propose(&builder, 1, 2, 2, 3),
+            ]);
+            let Some(Event::FrameFinalized(frame)) = result.generated_events.last() else {
+                panic!("expected a finalized frame");
+            };
+            assert_eq!(frame.chain_head, hash(3));
+            assert_eq!(frame.finalized_blocks, vec![hash(2), hash(3)]);
+            assert_eq!(state.frame_range(frame.frame_number), Some((1, 2)));
+        });
+    }
+
+    #[test]
+    fn test_deactivate_builder() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(0);
+            let mut state = State::new(context, config(vec![builder.public_key()]));
+            let deactivate = sign_transaction(&builder, 0, Instruction::DeactivateBuilder);
+
+            // Reject proposals once deactivated
+            let result = execute_state_transition(&mut state, vec![
+                deactivate.clone(),
+                propose(&builder, 1, 1, 1, 2),
+            ]);
+            assert_eq!(result.included_txs, vec![deactivate.clone()]);
+            assert_eq!(result.invalid_txs.len(), 1);
+            assert!(!state.builders[&builder.public_key()].active);
+            assert_eq!(state.fork_tree.score(hash(2)), None);
+
+            // The account is kept, so the deactivation can't be replayed
+            let result = execute_state_transition(&mut state, vec![deactivate]);
+            assert_eq!(result.invalid_txs.len(), 1);
+            assert_eq!(state.builders[&builder.public_key()].nonce, 2);
+        });
+    }
+
+    #[test]
+    fn test_block_proposed_events() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let (alice, bob) = (PrivateKey::from_seed(0), PrivateKey::from_seed(1));
+            let mut state = State::new(context, config(vec![alice.public_key(), bob.public_key()]));
+
+            // Only accepted proposals emit an event
+            let result = execute_state_transition(&mut state, vec![
+                propose(&alice, 0, 1, 1, 2),
+                propose(&bob, 0, 1, 9, 3),
+                propose(&bob, 1, 2, 2, 4),
+            ]);
+            assert_eq!(result.generated_events, vec![
+                Event::BlockProposed(ProposedBlock { builder: alice.public_key(), height: 1, block_hash: hash(2) }),
+                Event::BlockProposed(ProposedBlock { builder: bob.public_key(), height: 2, block_hash: hash(4) }),
+            ]);
+        });
+    }
+
+    #[test]
+    fn test_last_nonce() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(0);
+            let mut state = State::new(context, config(vec![builder.public_key()]));
+            state.builders.get_mut(&builder.public_key()).unwrap().nonce = u64::MAX;
+
+            // Reject transactions at the last nonce
+            let result = execute_state_transition(&mut state, vec![delegate(&builder, u64::MAX, 1)]);
+            assert!(result.included_txs.is_empty());
+            assert_eq!(result.invalid_txs.len(), 1);
+            let account = &state.builders[&builder.public_key()];
+            assert_eq!((account.nonce, account.stake), (u64::MAX, 0));
+        });
+    }
+
+    #[test]
+    fn test_withdraw_stake() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(0);
+            let mut state = State::new(context, config(vec![builder.public_key()]));
+
+            // Nothing to withdraw while active
+            let result = execute_state_transition(&mut state, vec![
+                delegate(&builder, 0, 10),
+                sign_transaction(&builder, 1, Instruction::WithdrawStake),
+            ]);
+            assert_eq!(result.included_txs.len(), 1);
+            assert_eq!(result.invalid_txs.len(), 1);
+
+            // Release the stake on deactivation
+            let result = execute_state_transition(&mut state, vec![
+                sign_transaction(&builder, 2, Instruction::DeactivateBuilder),
+            ]);
+            assert_eq!(result.included_txs.len(), 1);
+            let account = &state.builders[&builder.public_key()];
+            assert_eq!((account.stake, account.withdrawable_stake), (0, 10));
+
+            // Withdraw it once
+            let result = execute_state_transition(&mut state, vec![
+                sign_transaction(&builder, 3, Instruction::WithdrawStake),
+                sign_transaction(&builder, 4, Instruction::WithdrawStake),
+            ]);
+            assert_eq!(result.included_txs.len(), 1);
+            assert_eq!(result.invalid_txs.len(), 1);
+            assert_eq!(result.generated_events, vec![
+                Event::StakeWithdrawn(WithdrawnStake { builder: builder.public_key(), amount: 10 }),
+            ]);
+            assert_eq!(state.builders[&builder.public_key()].withdrawable_stake, 0);
+        });
+    }
+
+    #[test]
+    fn test_frame_ranges() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(0);
+            let mut config = config(vec![builder.public_key()]);
+            config.finalization_trigger = FinalizationTrigger::ProposalCount(2);
+            let mut state = State::new(context, config);
+
+            // Finalize heights 1 to 2, then 3 to 4
+            execute_state_transition(&mut state, vec![
+                propose(&builder, 0, 1, 1, 2),
+                propose(&builder, 1, 2, 2, 3),
+            ]);
+            execute_state_transition(&mut state, vec![
+                propose(&builder, 2, 3, 3, 4),
+                propose(&builder, 3, 4, 4, 5),
+            ]);
+            assert_eq!(state.fork_tree.finalized_frame(), 3);
+            assert_eq!(state.frame_range(2), Some((1, 2)));
+            assert_eq!(state.frame_range(3), Some((3, 4)));
+
+            // The genesis frame (and future frames) finalize no blocks
+            assert_eq!(state.frame_range(1), None);
+            assert_eq!(state.frame_range(4), None);
+        });
+    }
+
+    #[test]
+    fn test_genesis_builders() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let (alice, bob) = (PrivateKey::from_seed(0), PrivateKey::from_seed(1));
+            let mut state = State::new(context, config(vec![alice.public_key(), bob.public_key()]));
+            for builder in [&alice, &bob] {
+                assert_eq!(state.builders[&builder.public_key()], BuilderAccount::default());
+            }
+
+            // Both can propose right away, unlike unknown builders
+            let carol = PrivateKey::from_seed(2);
+            let result = execute_state_transition(&mut state, vec![
+                propose(&alice, 0, 1, 1, 2),
+                propose(&bob, 0, 1, 1, 3),
+                propose(&carol, 0, 1, 1, 4),
+            ]);
+            assert_eq!(result.included_txs.len(), 2);
+            assert_eq!(result.invalid_txs.len(), 1);
+            assert_eq!(state.fork_tree.score(hash(2)), Some(1));
+            assert_eq!(state.fork_tree.score(hash(3)), Some(1));
+            assert_eq!(state.fork_tree.score(hash(4)), None);
+        });
+    }
+
+    #[test]
+    fn test_builder_quorum_overflow() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builders = (0..3).map(PrivateKey::from_seed).collect::<Vec<_>>();
+            let mut config = config(builders.iter().map(|builder| builder.public_key()).collect());
+            config.finalization_trigger = FinalizationTrigger::BuilderQuorum {
+                numerator: u64::MAX - 1,
+                denominator: u64::MAX,
+            };
+            let mut state = State::new(context, config);
+
+            // Two of three builders stay below a quorum close to every builder
+            execute_state_transition(&mut state, vec![
+                propose(&builders[0], 0, 1, 1, 2),
+                propose(&builders[1], 0, 1, 1, 2),
+            ]);
+            assert!(!state.should_finalize());
+
+            // The last one reaches it
+            let result = execute_state_transition(&mut state, vec![propose(&builders[2], 0, 1, 1, 2)]);
+            assert!(matches!(result.generated_events.last(), Some(Event::FrameFinalized(_))));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "builder quorum must be positive")]
+    fn test_builder_quorum_zero_denominator() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut config = config(Vec::new());
+            config.finalization_trigger = FinalizationTrigger::BuilderQuorum { numerator: 0, denominator: 0 };
+            State::new(context, config);
+        });
+    }
 }
\ No newline at end of file
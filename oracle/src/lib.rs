@@ -1,4 +1,8 @@
 pub mod types;
 pub mod execution;
 pub mod wire;
-pub mod actor;
\ No newline at end of file
+pub mod actor;
+pub mod mailbox;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod testkit;
\ No newline at end of file
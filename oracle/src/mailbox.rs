@@ -0,0 +1,66 @@
+use futures::{
+    channel::{mpsc, oneshot},
+    SinkExt,
+};
+
+use crate::types::Frame;
+
+/// A query answered by the oracle from its current state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Query {
+    /// Frames finalized since the given frame number (included), from the oldest to the latest.
+    FinalizedFramesSince(u64),
+}
+
+/// The answer to a [Query].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueryResponse {
+    FinalizedFrames(Vec<Frame>),
+}
+
+pub(crate) struct Message {
+    pub query: Query,
+    pub response: oneshot::Sender<QueryResponse>,
+}
+
+/// Stops a running oracle once its current tick is done.
+pub struct ShutdownHandle {
+    sender: mpsc::Sender<()>,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new(sender: mpsc::Sender<()>) -> Self {
+        Self { sender }
+    }
+
+    /// Signal the oracle to shut down (await the task handle to wait for it to exit).
+    pub async fn shutdown(mut self) {
+        let _ = self.sender.send(()).await;
+    }
+}
+
+/// Sends queries to a running oracle.
+#[derive(Clone)]
+pub struct Mailbox {
+    sender: mpsc::Sender<Message>,
+}
+
+impl Mailbox {
+    pub(crate) fn new(sender: mpsc::Sender<Message>) -> Self {
+        Self { sender }
+    }
+
+    /// Send a query to the oracle and wait for its response (`None` if the oracle stopped).
+    pub async fn query(&mut self, query: Query) -> Option<QueryResponse> {
+        let (response, receiver) = oneshot::channel();
+        self.sender.send(Message { query, response }).await.ok()?;
+        receiver.await.ok()
+    }
+
+    /// Get the frames finalized since the given frame number (included).
+    pub async fn finalized_frames_since(&mut self, frame_number: u64) -> Option<Vec<Frame>> {
+        match self.query(Query::FinalizedFramesSince(frame_number)).await? {
+            QueryResponse::FinalizedFrames(frames) => Some(frames),
+        }
+    }
+}
\ No newline at end of file
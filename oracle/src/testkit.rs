@@ -0,0 +1,266 @@
+use std::time::Duration;
+
+use commonware_codec::{Decode, Encode};
+use commonware_cryptography::{
+    ed25519::{PrivateKey, PublicKey}, Digestible, PrivateKeyExt, Signer
+};
+use commonware_runtime::{Clock, Handle, Metrics, Spawner, Storage};
+use commonware_p2p::{
+    simulated::{self, Link, Network},
+    Receiver, Recipients, Sender,
+};
+
+use rand::{CryptoRng, Rng};
+use governor::clock::Clock as GClock;
+
+use fcn_common::mempool::MempoolConfig;
+use crate::{
+    actor::{Actor, Config},
+    execution::FinalizationTrigger,
+    mailbox::{Mailbox, ShutdownHandle},
+    types::{Instruction, Transaction},
+    wire::MessageEvent,
+};
+
+/// Channel used to submit transactions to the oracle.
+pub const TX_CHANNEL: u32 = 0;
+
+/// Channel used by the oracle to broadcast events.
+pub const EVENT_CHANNEL: u32 = 1;
+
+/// The maximum size of a message on the test network.
+const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// Seed of the client key connected to the test oracle.
+const CLIENT_SEED: u64 = u64::MAX;
+
+/// Get the public key of the client connected to the test oracle.
+pub fn client_key() -> PublicKey {
+    PrivateKey::from_seed(CLIENT_SEED).public_key()
+}
+
+/// Get an oracle config minting a block every second and finalizing a frame on every
+/// proposal, with no genesis builders.
+pub fn default_config(event_signer: PrivateKey) -> Config {
+    Config {
+        genesis_block_hash: [1; 32].into(),
+        genesis_parent: [0; 32].into(),
+        max_fork_depth: None,
+        score_decay: None,
+        hysteresis: 0,
+        frame_retention: None,
+        genesis_builders: Vec::new(),
+
+        block_period: Duration::from_secs(1),
+        align_to_epoch: false,
+        finalization_trigger: FinalizationTrigger::ProposalCount(1),
+        max_finalization_attempts: 3,
+        max_proposals_per_builder_per_frame: 16,
+        max_builder_stake: u64::MAX,
+        mempool: MempoolConfig::default(),
+
+        event_signer,
+        frame_recipients: Vec::new(),
+        rng_seed: Some(0),
+    }
+}
+
+/// Create a transaction signed by the given key.
+pub fn sign_transaction(signer: &PrivateKey, nonce: u64, instruction: Instruction) -> Transaction {
+    let mut tx = Transaction {
+        nonce,
+        instruction,
+        public_key: signer.public_key(),
+        signature: signer.sign(None, &[]),
+    };
+    tx.signature = signer.sign(None, &tx.digest());
+    tx
+}
+
+/// Submits transactions to a test oracle.
+pub struct TxSender {
+    oracle: PublicKey,
+    sender: simulated::Sender<PublicKey>,
+}
+
+impl TxSender {
+    pub async fn submit(&mut self, tx: &Transaction) -> bool {
+        self.sender
+            .send(Recipients::One(self.oracle.clone()), tx.encode().into(), false)
+            .await
+            .map(|sent| !sent.is_empty())
+            .unwrap_or(false)
+    }
+}
+
+/// Receives events broadcast by a test oracle.
+pub struct EventReceiver {
+    receiver: simulated::Receiver<PublicKey>,
+}
+
+impl EventReceiver {
+    /// Wait for the next event, skipping messages that fail to decode.
+    pub async fn next(&mut self) -> Option<MessageEvent> {
+        loop {
+            let (_, msg) = self.receiver.recv().await.ok()?;
+            if let Ok(event) = MessageEvent::decode_cfg(msg, &()) {
+                return Some(event);
+            }
+        }
+    }
+}
+
+/// An oracle running on an in-memory network, with the client side of its channels.
+pub struct TestOracle {
+    pub tx_sender: TxSender,
+    pub events: EventReceiver,
+    pub mailbox: Mailbox,
+    pub shutdown: ShutdownHandle,
+    pub handle: Handle<()>,
+
+    network: simulated::Oracle<PublicKey>,
+    oracle_key: PublicKey,
+    client_key: PublicKey,
+}
+
+impl TestOracle {
+    /// Cut the link from the oracle to the client, so none of its events can be delivered.
+    pub async fn disconnect_events(&mut self) {
+        self.network
+            .remove_link(self.oracle_key.clone(), self.client_key.clone())
+            .await
+            .expect("failed to unlink oracle from client");
+    }
+
+    /// Restore the link from the oracle to the client.
+    pub async fn connect_events(&mut self) {
+        self.network
+            .add_link(self.oracle_key.clone(), self.client_key.clone(), link())
+            .await
+            .expect("failed to link oracle to client");
+    }
+}
+
+/// Get the link between the oracle and the client.
+fn link() -> Link {
+    Link {
+        latency: Duration::from_millis(1),
+        jitter: Duration::ZERO,
+        success_rate: 1.0,
+    }
+}
+
+/// Spawn an oracle connected to a single client over an in-memory network.
+pub async fn spawn_test_oracle<E>(context: E, config: Config) -> TestOracle
+where
+    E: Clock + GClock + Rng + CryptoRng + Spawner + Storage + Metrics,
+{
+    let oracle_key = config.event_signer.public_key();
+    let (actor, mailbox) = Actor::new(context.with_label("oracle"), config).await;
+    start_test_oracle(context, oracle_key, actor, mailbox).await
+}
+
+/// Start an oracle created with the given key, connected to a single client over an
+/// in-memory network.
+pub async fn start_test_oracle<E>(
+    context: E,
+    oracle_key: PublicKey,
+    actor: Actor<E>,
+    mailbox: Mailbox,
+) -> TestOracle
+where
+    E: Clock + GClock + Rng + CryptoRng + Spawner + Storage + Metrics,
+{
+    // Start network
+    let (network, mut oracle) = Network::new(
+        context.with_label("network"),
+        simulated::Config {
+            max_size: MAX_MESSAGE_SIZE,
+            disconnect_on_block: false,
+        },
+    );
+    network.start();
+
+    // Register peers
+    let client_key = client_key();
+    let (_, tx_receiver) = oracle
+        .register(oracle_key.clone(), TX_CHANNEL)
+        .await
+        .expect("failed to register oracle transaction channel");
+    let event_network = oracle
+        .register(oracle_key.clone(), EVENT_CHANNEL)
+        .await
+        .expect("failed to register oracle event channel");
+    let (tx_sender, _) = oracle
+        .register(client_key.clone(), TX_CHANNEL)
+        .await
+        .expect("failed to register client transaction channel");
+    let (_, event_receiver) = oracle
+        .register(client_key.clone(), EVENT_CHANNEL)
+        .await
+        .expect("failed to register client event channel");
+
+    // Connect peers
+    oracle
+        .add_link(client_key.clone(), oracle_key.clone(), link())
+        .await
+        .expect("failed to link client to oracle");
+    oracle
+        .add_link(oracle_key.clone(), client_key.clone(), link())
+        .await
+        .expect("failed to link oracle to client");
+
+    // Start oracle
+    let (handle, shutdown) = actor.start(tx_receiver, event_network);
+
+    TestOracle {
+        tx_sender: TxSender { oracle: oracle_key.clone(), sender: tx_sender },
+        events: EventReceiver { receiver: event_receiver },
+        mailbox,
+        shutdown,
+        handle,
+
+        network: oracle,
+        oracle_key,
+        client_key,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commonware_runtime::{deterministic, Runner};
+
+    use crate::types::BlockProposal;
+
+    #[test]
+    fn test_finalize_proposal() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let builder = PrivateKey::from_seed(1);
+            let mut config = default_config(PrivateKey::from_seed(0));
+            config.genesis_builders = vec![builder.public_key()];
+            let mut oracle = spawn_test_oracle(context, config).await;
+
+            // Submit a proposal
+            let tx = sign_transaction(&builder, 0, Instruction::ProposeBlock(BlockProposal {
+                block_height: 1,
+                parent_hash: [1; 32].into(),
+                block_hash: [2; 32].into(),
+            }));
+            assert!(oracle.tx_sender.submit(&tx).await);
+
+            // Wait for the frame it finalizes
+            let frame = loop {
+                match oracle.events.next().await {
+                    Some(MessageEvent::FrameFinalized(frame)) => break frame,
+                    Some(_) => continue,
+                    None => panic!("oracle stopped"),
+                }
+            };
+            assert_eq!(frame.frame_number, 2);
+            assert_eq!(frame.chain_head, [2; 32].into());
+            assert_eq!(frame.finalized_blocks, vec![[2; 32].into()]);
+        });
+    }
+}
\ No newline at end of file
@@ -1,11 +1,13 @@
+use std::cmp::Ordering;
+
 use commonware_cryptography::{
-    Digestible, Hasher,
+    Digestible, Hasher, Verifier,
     ed25519::{PublicKey, Signature},
     sha256::{Digest, Sha256},
 };
 use commonware_codec::{
     Write, Read, EncodeSize, Error as CodecError,
-    Encode, ReadExt, 
+    Encode, DecodeExt, ReadExt, RangeCfg,
 };
 
 use bytes::{Buf, BufMut};
@@ -55,6 +57,14 @@ impl Read for Transaction {
     }
 }
 
+impl TryFrom<&[u8]> for Transaction {
+    type Error = CodecError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::decode(bytes)
+    }
+}
+
 impl MempoolTransaction for Transaction {
     fn public_key(&self) -> PublicKey {
         self.public_key.clone()
@@ -65,11 +75,15 @@ impl MempoolTransaction for Transaction {
     }
 }
 
-impl Digestible for Transaction {
-    type Digest = Digest;
+impl Transaction {
+    /// Check the signature of the transaction against its public key.
+    pub fn verify_signature(&self) -> bool {
+        self.public_key.verify(None, &self.digest(), &self.signature)
+    }
 
-    fn digest(&self) -> Digest {
-        let mut hasher = Sha256::new();
+    /// Compute the transaction digest with the given hasher.
+    pub fn digest_with<H: Hasher>(&self) -> H::Digest {
+        let mut hasher = H::new();
         hasher.update(self.nonce.to_be_bytes().as_ref());
         hasher.update(self.instruction.encode().as_ref());
         hasher.update(self.public_key.as_ref());
@@ -79,9 +93,22 @@ impl Digestible for Transaction {
     }
 }
 
+impl Digestible for Transaction {
+    type Digest = Digest;
+
+    fn digest(&self) -> Digest {
+        self.digest_with::<Sha256>()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Instruction {
     ProposeBlock(BlockProposal),
+    DelegateStake(DelegateStake),
+    /// Permanently stop accepting block proposals from the signer (releasing its stake).
+    DeactivateBuilder,
+    /// Withdraw the stake released by a deactivation.
+    WithdrawStake,
 }
 
 impl Write for Instruction {
@@ -91,6 +118,16 @@ impl Write for Instruction {
                 0u8.write(buf);
                 i.write(buf);
             }
+            Instruction::DelegateStake(i) => {
+                1u8.write(buf);
+                i.write(buf);
+            }
+            Instruction::DeactivateBuilder => {
+                2u8.write(buf);
+            }
+            Instruction::WithdrawStake => {
+                3u8.write(buf);
+            }
         }
     }
 }
@@ -98,7 +135,9 @@ impl Write for Instruction {
 impl EncodeSize for Instruction {
     fn encode_size(&self) -> usize {
         1 + match self {
-            Instruction::ProposeBlock(i) => i.encode_size()
+            Instruction::ProposeBlock(i) => i.encode_size(),
+            Instruction::DelegateStake(i) => i.encode_size(),
+            Instruction::DeactivateBuilder | Instruction::WithdrawStake => 0,
         }
     }
 }
@@ -109,6 +148,9 @@ impl Read for Instruction {
         let tag = u8::read(buf)?;
         match tag {
             0 => Ok(Instruction::ProposeBlock(BlockProposal::read(buf)?)),
+            1 => Ok(Instruction::DelegateStake(DelegateStake::read(buf)?)),
+            2 => Ok(Instruction::DeactivateBuilder),
+            3 => Ok(Instruction::WithdrawStake),
             d => Err(CodecError::InvalidEnum(d)),
         }
     }
@@ -151,21 +193,93 @@ impl Read for BlockProposal {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DelegateStake {
+    pub amount: u64,
+}
+
+impl Write for DelegateStake {
+    fn write(&self, buf: &mut impl BufMut) {
+        self.amount.write(buf);
+    }
+}
+
+impl EncodeSize for DelegateStake {
+    fn encode_size(&self) -> usize {
+        self.amount.encode_size()
+    }
+}
+
+impl Read for DelegateStake {
+    type Cfg = ();
+    fn read_cfg(buf: &mut impl Buf, _: &()) -> Result<Self, CodecError> {
+        let amount = u64::read(buf)?;
+        Ok(Self{
+            amount,
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Event {
-    FrameFinalized(Frame)
+    BlockProposed(ProposedBlock),
+    FrameFinalized(Frame),
+    FinalizationStalled(u64),
+    StakeWithdrawn(WithdrawnStake),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProposedBlock {
+    pub builder: PublicKey,
+    pub height: u64,
+    pub block_hash: Digest,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WithdrawnStake {
+    pub builder: PublicKey,
+    pub amount: u64,
 }
 
+/// The maximum number of blocks finalized by a single frame.
+pub const MAX_FRAME_BLOCKS: usize = 1024;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Frame {
     pub frame_number: u64,
     pub chain_head: Digest,
+    /// Blocks finalized by the frame, from the oldest to the chain head.
+    pub finalized_blocks: Vec<Digest>,
+}
+
+impl Frame {
+    /// Check if the frame was finalized after the other one.
+    pub fn is_newer_than(&self, other: &Frame) -> bool {
+        self.frame_number > other.frame_number
+    }
+}
+
+impl Ord for Frame {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Order by frame number (other fields only keep the order consistent with Eq)
+        self.frame_number
+            .cmp(&other.frame_number)
+            .then_with(|| self.chain_head.cmp(&other.chain_head))
+            .then_with(|| self.finalized_blocks.cmp(&other.finalized_blocks))
+    }
+}
+
+impl PartialOrd for Frame {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl Write for Frame {
     fn write(&self, buf: &mut impl BufMut) {
         self.frame_number.write(buf);
         self.chain_head.write(buf);
+        self.finalized_blocks.write(buf);
     }
 }
 
@@ -173,6 +287,7 @@ impl EncodeSize for Frame {
     fn encode_size(&self) -> usize {
         self.frame_number.encode_size()
             + self.chain_head.encode_size()
+            + self.finalized_blocks.encode_size()
     }
 }
 
@@ -181,14 +296,194 @@ impl Read for Frame {
     fn read_cfg(buf: &mut impl Buf, _: &()) -> Result<Self, CodecError> {
         let frame = u64::read(buf)?;
         let head = Digest::read(buf)?;
+        let blocks = Vec::<Digest>::read_cfg(
+            buf,
+            &(RangeCfg::from(0..=MAX_FRAME_BLOCKS), ()),
+        )?;
+        Ok(Self{
+            frame_number: frame,
+            chain_head: head,
+            finalized_blocks: blocks,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for Frame {
+    type Error = CodecError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::decode(bytes)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MintedBlock {
+    pub block_number: u64,
+    pub block_hash: Digest,
+}
+
+impl MintedBlock {
+    pub fn compute_hash(parent: &Digest, block_number: u64, transactions: &[Transaction]) -> Digest {
+        let mut hasher = Sha256::new();
+        hasher.update(parent);
+        hasher.update(&block_number.to_be_bytes());
+        for transaction in transactions {
+            hasher.update(&transaction.digest());
+        }
+        hasher.finalize()
+    }
+}
+
+impl Write for MintedBlock {
+    fn write(&self, buf: &mut impl BufMut) {
+        self.block_number.write(buf);
+        self.block_hash.write(buf);
+    }
+}
+
+impl EncodeSize for MintedBlock {
+    fn encode_size(&self) -> usize {
+        self.block_number.encode_size()
+            + self.block_hash.encode_size()
+    }
+}
+
+impl Read for MintedBlock {
+    type Cfg = ();
+    fn read_cfg(buf: &mut impl Buf, _: &()) -> Result<Self, CodecError> {
+        let number = u64::read(buf)?;
+        let hash = Digest::read(buf)?;
+        Ok(Self{
+            block_number: number,
+            block_hash: hash,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub frame_number: u64,
+    pub chain_head: Digest,
+    pub state_root: Digest,
+}
+
+impl Write for Checkpoint {
+    fn write(&self, buf: &mut impl BufMut) {
+        self.frame_number.write(buf);
+        self.chain_head.write(buf);
+        self.state_root.write(buf);
+    }
+}
+
+impl EncodeSize for Checkpoint {
+    fn encode_size(&self) -> usize {
+        self.frame_number.encode_size()
+            + self.chain_head.encode_size()
+            + self.state_root.encode_size()
+    }
+}
+
+impl Read for Checkpoint {
+    type Cfg = ();
+    fn read_cfg(buf: &mut impl Buf, _: &()) -> Result<Self, CodecError> {
+        let frame = u64::read(buf)?;
+        let head = Digest::read(buf)?;
+        let root = Digest::read(buf)?;
         Ok(Self{
             frame_number: frame,
             chain_head: head,
+            state_root: root,
         })
     }
 }
 
-#[derive(Clone, Default, Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Debug)]
 pub struct BuilderAccount {
     pub nonce: u64,
+    pub stake: u64,
+    pub proposals_in_frame: u64,
+    pub active: bool,
+    /// Stake released by a deactivation that can be withdrawn.
+    pub withdrawable_stake: u64,
+}
+
+impl Default for BuilderAccount {
+    fn default() -> Self {
+        Self {
+            nonce: 0,
+            stake: 0,
+            proposals_in_frame: 0,
+            active: true,
+            withdrawable_stake: 0,
+        }
+    }
+}
+
+impl BuilderAccount {
+    /// Weight of a single block proposal made by this builder.
+    pub fn proposal_weight(&self) -> u64 {
+        self.stake.saturating_add(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commonware_cryptography::{ed25519::PrivateKey, PrivateKeyExt, Signer};
+
+    use crate::testkit::sign_transaction;
+
+    fn frame(frame_number: u64, head: u8) -> Frame {
+        Frame {
+            frame_number,
+            chain_head: [head; 32].into(),
+            finalized_blocks: vec![[head; 32].into()],
+        }
+    }
+
+    #[test]
+    fn test_frame_ordering() {
+        let mut frames = vec![frame(3, 1), frame(1, 9), frame(2, 5), frame(1, 2)];
+        frames.sort();
+        let numbers = frames.iter().map(|frame| frame.frame_number).collect::<Vec<_>>();
+        assert_eq!(numbers, vec![1, 1, 2, 3]);
+        assert_eq!(frames[0], frame(1, 2));
+
+        // Ignore stale or replayed frames
+        let latest = frame(2, 5);
+        assert!(frame(3, 1).is_newer_than(&latest));
+        assert!(!frame(2, 5).is_newer_than(&latest));
+        assert!(!frame(2, 6).is_newer_than(&latest));
+        assert!(!frame(1, 9).is_newer_than(&latest));
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let signer = PrivateKey::from_seed(0);
+        let tx = sign_transaction(&signer, 0, Instruction::DeactivateBuilder);
+        assert!(tx.verify_signature());
+
+        // The signature covers the digest of the transaction
+        let mut tampered = tx.clone();
+        tampered.instruction = Instruction::WithdrawStake;
+        assert!(!tampered.verify_signature());
+
+        // Reject a signature from another key
+        let mut forged = tx;
+        forged.public_key = PrivateKey::from_seed(1).public_key();
+        assert!(!forged.verify_signature());
+    }
+
+    #[test]
+    fn test_try_from() {
+        let tx = sign_transaction(&PrivateKey::from_seed(0), 3, Instruction::WithdrawStake);
+        let encoded = tx.encode();
+        assert_eq!(Transaction::try_from(&encoded[..]).unwrap(), tx);
+        assert!(Transaction::try_from(&encoded[..encoded.len() - 1]).is_err());
+
+        let finalized = frame(4, 7);
+        let encoded = finalized.encode();
+        assert_eq!(Frame::try_from(&encoded[..]).unwrap(), finalized);
+        assert!(Frame::try_from(&encoded[..encoded.len() - 1]).is_err());
+    }
 }
\ No newline at end of file
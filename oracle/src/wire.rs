@@ -2,39 +2,64 @@ use commonware_cryptography::{
     sha256::{Digest, Sha256}, Committable, Digestible, Hasher
 };
 use commonware_codec::{
-    Encode, EncodeSize, Error as CodecError, Read, ReadExt, Write 
+    DecodeExt, Encode, EncodeSize, Error as CodecError, RangeCfg, Read, ReadExt, Write 
 };
 
 use bytes::{Buf, BufMut};
 
-use crate::types::Frame;
+use crate::types::{Checkpoint, Frame, MintedBlock};
+
+/// Version of the message event wire format (bumped on every layout change).
+///
+/// 2: `BlockMinted` carries the block hash.
+/// 3: `Frame` lists the blocks it finalized.
+pub const MESSAGE_EVENT_VERSION: u8 = 3;
+
+/// The maximum size of a gossiped transaction (in bytes).
+pub const MAX_GOSSIP_TRANSACTION_SIZE: usize = 1024;
 
 #[derive(Clone)]
 pub enum MessageEvent {
-    BlockMinted(u64),
+    BlockMinted(MintedBlock),
     FrameFinalized(Frame),
+    /// The expected state root of a finalized frame. The oracle doesn't execute swarm blocks,
+    /// so it never emits one itself.
+    Checkpoint(Checkpoint),
+    /// An encoded transaction relayed to peers that may have missed it.
+    TransactionGossip(Vec<u8>),
 }
 
 impl Write for MessageEvent {
     fn write(&self, buf: &mut impl BufMut) {
+        MESSAGE_EVENT_VERSION.write(buf);
         match self {
-            MessageEvent::BlockMinted(block_number) => {
+            MessageEvent::BlockMinted(block) => {
                 0u8.write(buf);
-                block_number.write(buf);
+                block.write(buf);
             }
             MessageEvent::FrameFinalized(frame) => {
                 1u8.write(buf);
                 frame.write(buf);
             },
+            MessageEvent::Checkpoint(checkpoint) => {
+                2u8.write(buf);
+                checkpoint.write(buf);
+            },
+            MessageEvent::TransactionGossip(tx) => {
+                3u8.write(buf);
+                tx.write(buf);
+            },
         }
     }
 }
 
 impl EncodeSize for MessageEvent {
     fn encode_size(&self) -> usize {
-        1 + match self {
-            MessageEvent::BlockMinted(block_number) => block_number.encode_size(),
+        2 + match self {
+            MessageEvent::BlockMinted(block) => block.encode_size(),
             MessageEvent::FrameFinalized(frame) => frame.encode_size(),
+            MessageEvent::Checkpoint(checkpoint) => checkpoint.encode_size(),
+            MessageEvent::TransactionGossip(tx) => tx.encode_size(),
         }
     }
 }
@@ -42,15 +67,33 @@ impl EncodeSize for MessageEvent {
 impl Read for MessageEvent {
     type Cfg = ();
     fn read_cfg(buf: &mut impl Buf, _: &()) -> Result<Self, CodecError> {
+        let version = u8::read(buf)?;
+        if version != MESSAGE_EVENT_VERSION {
+            return Err(CodecError::Invalid("MessageEvent", "unsupported version"));
+        }
+
         let tag = u8::read(buf)?;
         match tag {
-            0 => Ok(MessageEvent::BlockMinted(u64::read(buf)?)),
+            0 => Ok(MessageEvent::BlockMinted(MintedBlock::read(buf)?)),
             1 => Ok(MessageEvent::FrameFinalized(Frame::read(buf)?)),
+            2 => Ok(MessageEvent::Checkpoint(Checkpoint::read(buf)?)),
+            3 => Ok(MessageEvent::TransactionGossip(Vec::<u8>::read_cfg(
+                buf,
+                &(RangeCfg::from(0..=MAX_GOSSIP_TRANSACTION_SIZE), ()),
+            )?)),
             d => Err(CodecError::InvalidEnum(d)),
         }
     }
 }
 
+impl TryFrom<&[u8]> for MessageEvent {
+    type Error = CodecError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::decode(bytes)
+    }
+}
+
 impl Digestible for MessageEvent {
     type Digest = Digest;
 
@@ -65,4 +108,135 @@ impl Committable for MessageEvent {
     fn commitment(&self) -> Self::Commitment {
         self.digest()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commonware_cryptography::{ed25519::PrivateKey, PrivateKeyExt};
+
+    use crate::{
+        testkit::sign_transaction,
+        types::{Instruction, Transaction},
+    };
+
+    #[test]
+    fn test_version() {
+        // A minted block encoded by hand with the current version
+        let mut encoded = vec![MESSAGE_EVENT_VERSION, 0];
+        encoded.extend_from_slice(&7u64.to_be_bytes());
+        encoded.extend_from_slice(&[1; 32]);
+        let Ok(MessageEvent::BlockMinted(block)) = MessageEvent::decode(&encoded[..]) else {
+            panic!("failed to decode minted block");
+        };
+        assert_eq!(block, MintedBlock { block_number: 7, block_hash: [1; 32].into() });
+
+        // Unknown versions are rejected
+        encoded[0] = 99;
+        assert!(matches!(
+            MessageEvent::decode(&encoded[..]),
+            Err(CodecError::Invalid("MessageEvent", _))
+        ));
+    }
+
+    #[test]
+    fn test_checkpoint_codec() {
+        let checkpoint = Checkpoint {
+            frame_number: 3,
+            chain_head: [2; 32].into(),
+            state_root: [3; 32].into(),
+        };
+        let event = MessageEvent::Checkpoint(checkpoint.clone());
+        let encoded = event.encode();
+        assert_eq!(encoded.len(), event.encode_size());
+        let Ok(MessageEvent::Checkpoint(decoded)) = MessageEvent::decode(encoded.clone()) else {
+            panic!("failed to decode checkpoint");
+        };
+        assert_eq!(decoded, checkpoint);
+
+        // Tags of the existing variants are unchanged
+        let minted = MessageEvent::BlockMinted(MintedBlock {
+            block_number: 1,
+            block_hash: [1; 32].into(),
+        });
+        let frame = MessageEvent::FrameFinalized(Frame {
+            frame_number: 2,
+            chain_head: [1; 32].into(),
+            finalized_blocks: vec![[1; 32].into()],
+        });
+        assert_eq!(minted.encode()[1], 0);
+        assert_eq!(frame.encode()[1], 1);
+        assert_eq!(encoded[1], 2);
+    }
+
+    #[test]
+    fn test_block_minted_codec() {
+        let block = MintedBlock {
+            block_number: 5,
+            block_hash: MintedBlock::compute_hash(&[4; 32].into(), 5, &[]),
+        };
+        let event = MessageEvent::BlockMinted(block.clone());
+        let encoded = event.encode();
+        assert_eq!(encoded.len(), event.encode_size());
+        let Ok(MessageEvent::BlockMinted(decoded)) = MessageEvent::decode(encoded) else {
+            panic!("failed to decode minted block");
+        };
+        assert_eq!(decoded, block);
+    }
+
+    #[test]
+    fn test_transaction_gossip_codec() {
+        let tx = sign_transaction(&PrivateKey::from_seed(0), 1, Instruction::DeactivateBuilder);
+
+        // Round trip the encoded transaction
+        let event = MessageEvent::TransactionGossip(tx.encode().to_vec());
+        let encoded = event.encode();
+        assert_eq!(encoded.len(), event.encode_size());
+        assert_eq!(encoded[1], 3);
+        let Ok(MessageEvent::TransactionGossip(decoded)) = MessageEvent::decode(encoded) else {
+            panic!("failed to decode gossiped transaction");
+        };
+        assert_eq!(Transaction::decode(&decoded[..]).unwrap(), tx);
+
+        // Reject transactions above the maximum size
+        let event = MessageEvent::TransactionGossip(vec![0; MAX_GOSSIP_TRANSACTION_SIZE + 1]);
+        assert!(MessageEvent::decode(event.encode()).is_err());
+    }
+
+    #[test]
+    fn test_try_from() {
+        let frame = Frame {
+            frame_number: 2,
+            chain_head: [2; 32].into(),
+            finalized_blocks: vec![[1; 32].into(), [2; 32].into()],
+        };
+        let encoded = MessageEvent::FrameFinalized(frame.clone()).encode();
+        let Ok(MessageEvent::FrameFinalized(decoded)) = MessageEvent::try_from(&encoded[..]) else {
+            panic!("failed to decode finalized frame");
+        };
+        assert_eq!(decoded, frame);
+
+        // Reject truncated events
+        assert!(MessageEvent::try_from(&encoded[..encoded.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_previous_versions() {
+        // A minted block in the v1 layout (without its hash)
+        let mut v1 = vec![1, 0];
+        v1.extend_from_slice(&7u64.to_be_bytes());
+
+        // A finalized frame in the v2 layout (without its blocks)
+        let mut v2 = vec![2, 1];
+        v2.extend_from_slice(&2u64.to_be_bytes());
+        v2.extend_from_slice(&[1; 32]);
+
+        // Older layouts can't be decoded into the current events, so they are rejected
+        for encoded in [v1, v2] {
+            assert!(matches!(
+                MessageEvent::decode(&encoded[..]),
+                Err(CodecError::Invalid("MessageEvent", "unsupported version"))
+            ));
+        }
+    }
 }
\ No newline at end of file
@@ -1,26 +1,129 @@
 use std::{
     collections::BTreeMap,
+    num::NonZeroU64,
 };
 
 use commonware_codec::Encode;
 use commonware_cryptography::{
-    ed25519::PublicKey,
+    ed25519::{Batch, PublicKey},
     sha256::{Digest, Sha256},
-    Hasher,
+    BatchVerifier, Digestible, Hasher,
 };
 use commonware_runtime::{Clock, Metrics, Spawner, Storage};
 use commonware_storage::{
-    mmr::hasher::Standard,
+    mmr::{hasher::Standard, Proof},
     translator::Translator,
-    adb::any::variable::Any
+    adb::{any::variable::Any, Error as AdbError},
+    store::operation::Variable as Operation,
 };
 
+use futures::channel::mpsc;
+use prometheus_client::metrics::counter::Counter;
+use rand::thread_rng;
+use thiserror::Error;
+
 use crate::types::{
-    Account, CommitMetadata, 
-    Transaction, Instruction, TransferBread,
+    Account, Block, CommitMetadata, 
+    Transaction, Instruction, TransferBread, MintBread, UpdateMinters,
     Key, Value,
+    MAX_BLOCK_TRANSACTIONS,
 };
 
+/// The maximum number of operations read from the adb log at once.
+const OPERATIONS_BATCH: NonZeroU64 = NonZeroU64::new(1024).unwrap();
+
+#[derive(Error, Debug)]
+pub enum StateError {
+    #[error("adb error: {0}")]
+    Adb(#[from] AdbError),
+    #[error("adb error for key {0:?}: {1}")]
+    Key(Key, #[source] AdbError),
+    #[error("history unavailable for height")]
+    HistoryUnavailable(u64),
+    #[error("height above maximum")]
+    HeightLimit(u64),
+    #[error("snapshot doesn't match its root")]
+    InvalidSnapshot,
+}
+
+pub struct Config {
+    /// Chain transactions must be signed for.
+    pub chain_id: u64,
+    /// Head of the state before any block is committed.
+    pub genesis_block_hash: Digest,
+    /// Key allowed to update the set of authorized minters.
+    pub governance_key: PublicKey,
+    /// Minimum amount of a transfer (unless it sweeps the whole sender balance).
+    pub min_transfer_amount: u64,
+    /// Maximum height the state may be transitioned to.
+    pub max_height: Option<u64>,
+    /// Channel receiving the events of every applied state transition.
+    pub event_sender: Option<mpsc::UnboundedSender<ExecutionEvent>>,
+}
+
+/// A dump of the current state used to sync new nodes without replaying history.
+///
+/// It holds the operations from the inactivity floor of the last commit to the end of the
+/// log (which include the last write of every active key), proven against the state root.
+#[derive(Clone)]
+pub struct Snapshot {
+    /// Location of the first operation in the log.
+    pub start_loc: u64,
+    /// Operations up to (and including) the last commit.
+    pub operations: Vec<Operation<Digest, Value>>,
+    pub proof: Proof<Digest>,
+    /// Root of the exporting state (the root of an imported state differs, as it commits
+    /// to the operation history rather than only to the current values).
+    pub root: Digest,
+}
+
+impl Snapshot {
+    /// Check the operations are the end of the log committed to by the root, starting at
+    /// or below the inactivity floor of their last commit, and return the commit metadata
+    /// and the current value of every active key.
+    pub fn verify(&self) -> Option<(CommitMetadata, BTreeMap<Digest, Value>)> {
+        // The operations must end the log (so no later operation overrides them)
+        let leaves = self.start_loc.checked_add(self.operations.len() as u64)?;
+        if self.proof.size != mmr_size(leaves) {
+            return None;
+        }
+        let elements = self.operations.iter().map(|op| op.encode()).collect::<Vec<_>>();
+        let mut hasher = Standard::<Sha256>::new();
+        if self.proof.reconstruct_root(&mut hasher, &elements, self.start_loc).ok()? != self.root {
+            return None;
+        }
+
+        // Every active key must be written in the operations
+        let Some(Operation::CommitFloor(Some(Value::CommitMetadata(metadata)), floor)) =
+            self.operations.last()
+        else {
+            return None;
+        };
+        if self.start_loc > *floor {
+            return None;
+        }
+
+        // Replay the operations
+        let mut entries = BTreeMap::new();
+        for op in &self.operations {
+            match op {
+                Operation::Update(key, value) => {
+                    entries.insert(*key, value.clone());
+                }
+                Operation::Delete(key) => {
+                    entries.remove(key);
+                }
+                _ => {}
+            }
+        }
+        Some((metadata.clone(), entries))
+    }
+}
+
+/// Number of nodes of an MMR with the given number of leaves.
+fn mmr_size(leaves: u64) -> u64 {
+    2 * leaves - leaves.count_ones() as u64
+}
 
 #[derive(Clone)]
 pub enum StateOperation {
@@ -28,12 +131,67 @@ pub enum StateOperation {
     Delete,
 }
 
+/// An effect of a transaction executed successfully.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExecutionEvent {
+    Transferred(Transfer),
+    Minted(Mint),
+    MintersUpdated(Vec<PublicKey>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transfer {
+    pub from: PublicKey,
+    pub to: PublicKey,
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mint {
+    pub minter: PublicKey,
+    pub to: PublicKey,
+    pub amount: u64,
+}
+
+impl ExecutionEvent {
+    fn from_transaction(tx: &Transaction) -> Option<Self> {
+        let event = match &tx.instruction {
+            Instruction::TransferBread(i) => ExecutionEvent::Transferred(Transfer {
+                from: tx.public_key.clone(),
+                to: i.to.clone(),
+                amount: i.amount,
+            }),
+            Instruction::TransferBreadMemo(i) => ExecutionEvent::Transferred(Transfer {
+                from: tx.public_key.clone(),
+                to: i.to.clone(),
+                amount: i.amount,
+            }),
+            Instruction::MintBread(i) => ExecutionEvent::Minted(Mint {
+                minter: tx.public_key.clone(),
+                to: i.to.clone(),
+                amount: i.amount,
+            }),
+            Instruction::UpdateMinters(i) => ExecutionEvent::MintersUpdated(i.minters.clone()),
+            Instruction::Noop => return None,
+        };
+        Some(event)
+    }
+}
+
 pub struct State<E, T>
 where
     E: Spawner + Metrics + Clock + Storage,
     T: Translator,
 {
     adb: Any<E, Digest, Value, Sha256, T>,
+    chain_id: u64,
+    genesis_block_hash: Digest,
+    governance_key: PublicKey,
+    min_transfer_amount: u64,
+    max_height: Option<u64>,
+    event_sender: Option<mpsc::UnboundedSender<ExecutionEvent>>,
+
+    invalid_transactions: Counter,
 }
 
 impl<E, T> State<E, T>
@@ -41,97 +199,293 @@ where
     E: Spawner + Metrics + Clock + Storage,
     T: Translator,
 {
-    pub async fn get(&self, key: &Key) -> Option<Value> {
+    pub fn new(context: impl Metrics, adb: Any<E, Digest, Value, Sha256, T>, config: Config) -> Self {
+        // Initialize metrics
+        let invalid_transactions = Counter::default();
+        context.register(
+            "invalid_transactions",
+            "Number of transactions rejected during state transitions",
+            invalid_transactions.clone(),
+        );
+
+        Self {
+            adb,
+            chain_id: config.chain_id,
+            genesis_block_hash: config.genesis_block_hash,
+            governance_key: config.governance_key,
+            min_transfer_amount: config.min_transfer_amount,
+            max_height: config.max_height,
+            event_sender: config.event_sender,
+
+            invalid_transactions,
+        }
+    }
+
+    /// Rebuild a state from a snapshot (checked against its root) on top of an empty adb.
+    pub async fn import_snapshot(
+        context: impl Metrics,
+        adb: Any<E, Digest, Value, Sha256, T>,
+        config: Config,
+        snapshot: Snapshot,
+    ) -> Result<Self, StateError> {
+        let (metadata, entries) = snapshot.verify().ok_or(StateError::InvalidSnapshot)?;
+        let mut state = Self::new(context, adb, config);
+        for (key, value) in entries {
+            state.adb.update(key, value).await?;
+        }
+
+        // Operations of the snapshot height all start from the beginning of the new log
+        let metadata = CommitMetadata {
+            start: 0,
+            ..metadata
+        };
+        state.adb.commit(Some(Value::CommitMetadata(metadata))).await?;
+        Ok(state)
+    }
+
+    /// Dump the operations holding the current value of every key, with their proof.
+    pub async fn export_snapshot(&self) -> Result<Snapshot, StateError> {
+        // Every active key was last written at or above the inactivity floor, which is never
+        // pruned
+        let start_loc = self.adb.inactivity_floor_loc();
+        let size = self.adb.op_count();
+        let Some(max_ops) = NonZeroU64::new(size - start_loc) else {
+            return Err(StateError::HistoryUnavailable(0));
+        };
+        let (proof, operations) = self.adb.historical_proof(size, start_loc, max_ops).await?;
+
+        let mut hasher = Standard::<Sha256>::new();
+        Ok(Snapshot {
+            start_loc,
+            operations,
+            proof,
+            root: self.root(&mut hasher),
+        })
+    }
+
+    pub async fn get(&self, key: &Key) -> Result<Option<Value>, StateError> {
+        let hashed = Sha256::hash(&key.encode());
+        self.adb.get(&hashed).await.map_err(|err| StateError::Key(key.clone(), err))
+    }
+
+    /// Get an account (the default account if it doesn't exist).
+    pub async fn account(&self, public: &PublicKey) -> Result<Account, StateError> {
+        match self.get(&Key::Account(public.clone())).await? {
+            Some(Value::Account(account)) => Ok(account),
+            _ => Ok(Account::default()),
+        }
+    }
+
+    /// Get the balance of an account (0 if the account doesn't exist).
+    pub async fn balance(&self, public: &PublicKey) -> Result<u64, StateError> {
+        Ok(self.account(public).await?.bread)
+    }
+
+    /// Get the next nonce expected from an account (0 if the account doesn't exist).
+    pub async fn processed_nonce(&self, public: &PublicKey) -> Result<u64, StateError> {
+        Ok(self.account(public).await?.nonce)
+    }
+
+    /// Get the value of a key as of the commit of the given height.
+    pub async fn get_at(&self, key: &Key, height: u64) -> Result<Option<Value>, StateError> {
         let key = Sha256::hash(&key.encode());
-        self.adb.get(&key).await.unwrap()
+        let Some((size, floor)) = self.locate_commit(height).await? else {
+            return Err(StateError::HistoryUnavailable(height));
+        };
+
+        // Every key active at this commit was last written at or above its inactivity floor,
+        // so only that part of the log must be replayed (and retained)
+        if floor < self.adb.oldest_retained_loc().unwrap_or(0) {
+            return Err(StateError::HistoryUnavailable(height));
+        }
+        let mut value = None;
+        self.for_each_operation(floor, size, |op| match op {
+            Operation::Update(k, v) if k == key => value = Some(v),
+            Operation::Delete(k) if k == key => value = None,
+            _ => {}
+        }).await?;
+        Ok(value)
+    }
+
+    /// Find the commit of the given height by walking back through the commits, returning the
+    /// number of operations up to (and including) its commit operation and its inactivity floor.
+    async fn locate_commit(&self, height: u64) -> Result<Option<(u64, u64)>, StateError> {
+        let oldest = self.adb.oldest_retained_loc().unwrap_or(0);
+
+        // The start of each commit is located right after the commit operation of the
+        // previous height
+        let mut size = self.adb.op_count();
+        while size > oldest {
+            let mut commit = None;
+            self.for_each_operation(size - 1, size, |op| commit = Some(op)).await?;
+            let Some(Operation::CommitFloor(Some(Value::CommitMetadata(metadata)), floor)) = commit else {
+                return Ok(None);
+            };
+            if metadata.height == height {
+                return Ok(Some((size, floor)));
+            }
+            if metadata.height < height || metadata.start == 0 {
+                return Ok(None);
+            }
+            size = metadata.start;
+        }
+        Ok(None)
+    }
+
+    /// Visit the operations in `[start, end)` of the adb log, reading them in batches.
+    async fn for_each_operation(
+        &self,
+        start: u64,
+        end: u64,
+        mut visit: impl FnMut(Operation<Digest, Value>),
+    ) -> Result<(), StateError> {
+        let mut loc = start;
+        while loc < end {
+            let (_, ops) = self.adb.historical_proof(end, loc, OPERATIONS_BATCH).await?;
+            if ops.is_empty() {
+                break;
+            }
+            loc += ops.len() as u64;
+            ops.into_iter().for_each(&mut visit);
+        }
+        Ok(())
     }
 
+    /// Apply changes and commit them with the given metadata, returning the updated and
+    /// deleted keys.
     pub async fn apply(
         &mut self, changes: Vec<(Key, StateOperation)>,
         commit_meta: CommitMetadata
-    ) {
+    ) -> Result<Vec<Key>, StateError> {
+        let mut keys = Vec::with_capacity(changes.len());
         for (key, op) in changes {
             match op {
-                StateOperation::Update(value) => self.insert(key, value).await,
-                StateOperation::Delete => self.delete(&key).await,
+                StateOperation::Update(value) => self.insert(key.clone(), value).await?,
+                StateOperation::Delete => self.delete(&key).await?,
             }
+            keys.push(key);
         }
-        self.adb.commit(Some(Value::CommitMetadata(commit_meta)))
-            .await
-            .unwrap();
+        self.adb.commit(Some(Value::CommitMetadata(commit_meta))).await?;
+        Ok(keys)
     }
 
-    async fn insert(&mut self, key: Key, value: Value) {
-        let key = Sha256::hash(&key.encode());
-        self.adb.update(key, value).await.unwrap();
+    async fn insert(&mut self, key: Key, value: Value) -> Result<(), StateError> {
+        let hashed = Sha256::hash(&key.encode());
+        self.adb.update(hashed, value).await.map_err(|err| StateError::Key(key, err))
     }
 
-    async fn delete(&mut self, key: &Key) {
-        let key = Sha256::hash(&key.encode());
-        self.adb.delete(key).await.unwrap();
+    async fn delete(&mut self, key: &Key) -> Result<(), StateError> {
+        let hashed = Sha256::hash(&key.encode());
+        self.adb.delete(hashed).await.map_err(|err| StateError::Key(key.clone(), err))?;
+        Ok(())
+    }
+
+    /// Prune operations older than the last `keep_operations` ones.
+    pub async fn prune(&mut self, keep_operations: u64) -> Result<(), StateError> {
+        // Never prune the operations of the current height, so its commit metadata stays
+        // readable, nor above the inactivity floor (the adb rejects such targets, as the
+        // operations above it hold the current state)
+        let commit = self.commit_metadata().await?;
+        let target = self.adb.op_count()
+            .saturating_sub(keep_operations)
+            .min(commit.start)
+            .min(self.adb.inactivity_floor_loc());
+        self.adb.prune(target).await?;
+        Ok(())
     }
 
     pub fn operation_count(&self) -> u64 {
         self.adb.op_count()
     }
     
-    pub async fn commit_metadata(&self) -> CommitMetadata {
-        let (state_height, state_start_op) = self.adb
+    pub async fn commit_metadata(&self) -> Result<CommitMetadata, StateError> {
+        let commit = self.adb
             .get_metadata()
-            .await
-            .unwrap()
+            .await?
             .and_then(|(_, v)| match v {
-                Some(Value::CommitMetadata(v)) => Some((v.height, v.start)),
+                Some(Value::CommitMetadata(v)) => Some(v),
                 _ => None,
             })
-            .unwrap_or((0, 0));
-        CommitMetadata{
-            height: state_height,
-            start: state_start_op,
-        }
+            .unwrap_or(CommitMetadata {
+                height: 0,
+                start: 0,
+                head: self.genesis_block_hash,
+            });
+        Ok(commit)
     }
 
     pub fn root(&self, hasher: &mut Standard<Sha256>) ->  Digest{
         self.adb.root(hasher)
     }
+
+    /// Get the state root as of the commit of the given height (if that commit is retained).
+    pub async fn root_at(&self, height: u64) -> Result<Option<Digest>, StateError> {
+        let Some((size, _)) = self.locate_commit(height).await? else {
+            return Ok(None);
+        };
+
+        // Reconstruct the root of the log as of the commit
+        let (proof, ops) = self.adb.historical_proof(size, size - 1, NonZeroU64::MIN).await?;
+        let elements = ops.iter().map(|op| op.encode()).collect::<Vec<_>>();
+        let mut hasher = Standard::<Sha256>::new();
+        Ok(proof.reconstruct_root(&mut hasher, &elements, size - 1).ok())
+    }
 }
 
 pub struct StateTransitionResult {
+    /// Whether the transactions were executed (false when re-executing the tip).
+    pub applied: bool,
     pub state_root: Digest,
     pub state_start_op: u64,
     pub state_end_op: u64,
     pub processed_nonces: BTreeMap<PublicKey, u64>,
     pub invalid_txs: Vec<Transaction>,
+    pub events: Vec<ExecutionEvent>,
 }
 
 pub async fn execute_state_transition<E, T>( 
     state: &mut State<E, T>,
     txs: Vec<Transaction>,
     height: u64,
-) -> StateTransitionResult
+    head: Digest,
+) -> Result<StateTransitionResult, StateError>
 where 
     E: Spawner + Metrics + Clock + Storage,
     T: Translator,
 {
-    let state_commit = state.commit_metadata().await;
+    let state_commit = state.commit_metadata().await?;
     assert!(
         height == state_commit.height || height == state_commit.height + 1,
         "state transition must be for next block or tip"
     );
+    if state.max_height.is_some_and(|max_height| height > max_height) {
+        return Err(StateError::HeightLimit(height));
+    }
 
+    let mut applied = false;
     let mut state_start_op = state_commit.start;
     let mut processed_nonces = BTreeMap::new();
     let mut invalid_txs = Vec::new();
+    let mut events = Vec::new();
     
     // Only process if this is the next block
     if height == state_commit.height + 1 {
         state_start_op = state.operation_count();
         let mut layer = StateLayer::new(state);
-        (processed_nonces, invalid_txs) = layer.execute(txs).await;
+        (processed_nonces, invalid_txs, events) = layer.execute(txs).await?;
+        state.invalid_transactions.inc_by(invalid_txs.len() as u64);
         state.apply(
             layer.commit(), 
-            CommitMetadata { height, start: state_start_op }
-        ).await;
+            CommitMetadata { height, start: state_start_op, head }
+        ).await?;
+        applied = true;
+
+        // Notify subscribers (a closed channel only means nobody is listening anymore)
+        if let Some(sender) = &state.event_sender {
+            for event in &events {
+                let _ = sender.unbounded_send(event.clone());
+            }
+        }
     }
 
     // Compute roots
@@ -139,15 +493,167 @@ where
     let state_root = state.root(&mut mmr_hasher);
     let state_end_op = state.operation_count();
 
-    StateTransitionResult{
+    Ok(StateTransitionResult{
+        applied,
         state_root,
         state_start_op,
         state_end_op,
         processed_nonces,
         invalid_txs,
+        events,
+    })
+}
+
+pub struct SimulationResult {
+    pub processed_nonces: BTreeMap<PublicKey, u64>,
+    pub invalid_txs: Vec<Transaction>,
+    pub events: Vec<ExecutionEvent>,
+    pub changes: Vec<(Key, StateOperation)>,
+}
+
+/// Execute transactions on top of the current state without committing the result.
+pub async fn simulate_transactions<E, T>(
+    state: &State<E, T>,
+    txs: Vec<Transaction>,
+) -> Result<SimulationResult, StateError>
+where 
+    E: Spawner + Metrics + Clock + Storage,
+    T: Translator,
+{
+    let mut layer = StateLayer::new(state);
+    let (processed_nonces, invalid_txs, events) = layer.execute(txs).await?;
+
+    Ok(SimulationResult {
+        processed_nonces,
+        invalid_txs,
+        events,
+        changes: layer.commit(),
+    })
+}
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("invalid block height")]
+    InvalidBlockHeight(u64),
+    #[error("block parent doesn't match the state head")]
+    ParentMismatch(Digest),
+    #[error("too many block transactions")]
+    TooManyTransactions(usize),
+    #[error("block too large")]
+    TooLarge(usize),
+    #[error("invalid transaction signature")]
+    InvalidSignature(usize),
+    #[error("state error: {0}")]
+    State(#[from] StateError),
+}
+
+/// Batch verify the signatures of all block transactions, returning the indices of the
+/// transactions with an invalid signature on failure.
+pub fn verify_block_signatures(block: &Block) -> Result<(), Vec<usize>> {
+    let mut batch = Batch::new();
+    for tx in &block.transactions {
+        batch.add(None, &tx.digest(), &tx.public_key, &tx.signature);
+    }
+    if batch.verify(&mut thread_rng()) {
+        return Ok(());
     }
+
+    // Find the offending transactions
+    Err(block.verify_transactions())
 }
 
+pub async fn import_block<E, T>(
+    state: &mut State<E, T>,
+    block: &Block,
+    max_block_bytes: Option<usize>,
+) -> Result<StateTransitionResult, ImportError>
+where 
+    E: Spawner + Metrics + Clock + Storage,
+    T: Translator,
+{
+    // Check block height
+    let state_commit = state.commit_metadata().await?;
+    if block.height != state_commit.height + 1 {
+        return Err(ImportError::InvalidBlockHeight(block.height));
+    }
+
+    // Check block parent
+    if block.parent != state_commit.head {
+        return Err(ImportError::ParentMismatch(block.parent));
+    }
+
+    // Check transaction count
+    if block.transactions.len() > MAX_BLOCK_TRANSACTIONS {
+        return Err(ImportError::TooManyTransactions(block.transactions.len()));
+    }
+
+    // Check block size
+    if let Some(max_block_bytes) = max_block_bytes {
+        let size = block.size_bytes();
+        if size > max_block_bytes {
+            return Err(ImportError::TooLarge(size));
+        }
+    }
+
+    // Check transaction signatures
+    if let Some(index) = verify_block_signatures(block).err().and_then(|invalid| invalid.first().copied()) {
+        return Err(ImportError::InvalidSignature(index));
+    }
+
+    Ok(execute_state_transition(state, block.transactions.clone(), block.height, block.digest()).await?)
+}
+
+#[derive(Error, Debug)]
+pub enum PrevalidationError {
+    #[error("too many invalid block transactions")]
+    TooManyInvalid(usize),
+    #[error("state error: {0}")]
+    State(#[from] StateError),
+}
+
+/// Cheaply check that a block isn't spam, rejecting it if more than `max_invalid_ratio` of
+/// its transactions come from an unknown sender or use an unexpected nonce.
+pub async fn prevalidate_block<E, T>(
+    state: &State<E, T>,
+    block: &Block,
+    max_invalid_ratio: f64,
+) -> Result<(), PrevalidationError>
+where 
+    E: Spawner + Metrics + Clock + Storage,
+    T: Translator,
+{
+    let mut next_nonces = BTreeMap::<PublicKey, Option<u64>>::new();
+    let mut invalid = 0;
+    for tx in StateLayer::<E, T>::order_transactions(block.transactions.clone()) {
+        // Get expected nonce of the sender
+        let next_nonce = match next_nonces.get_mut(&tx.public_key) {
+            Some(next_nonce) => next_nonce,
+            None => {
+                let account = match state.get(&Key::Account(tx.public_key.clone())).await? {
+                    Some(Value::Account(account)) => Some(account.nonce),
+                    _ => None,
+                };
+                next_nonces.entry(tx.public_key.clone()).or_insert(account)
+            }
+        };
+
+        // Check sender and nonce
+        match next_nonce {
+            Some(nonce) if *nonce == tx.nonce && *nonce < u64::MAX => *nonce += 1,
+            _ => invalid += 1,
+        }
+    }
+
+    if invalid as f64 > block.transactions.len() as f64 * max_invalid_ratio {
+        return Err(PrevalidationError::TooManyInvalid(invalid));
+    }
+    Ok(())
+}
+
+/// A point in the pending writes of a [StateLayer] that can be rolled back to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SavepointId(usize);
+
 pub struct StateLayer<'a, E, T>
 where
     E: Spawner + Metrics + Clock + Storage,
@@ -155,6 +661,8 @@ where
 {
     state: &'a State<E, T>,
     pending: BTreeMap<Key, StateOperation>,
+    /// Previous pending operation of every written key, in write order.
+    journal: Vec<(Key, Option<StateOperation>)>,
 }
 
 impl<'a, E, T> StateLayer<'a, E, T>
@@ -166,9 +674,29 @@ where
         Self {
             state,
             pending: BTreeMap::new(),
+            journal: Vec::new(),
         }
     }
 
+    /// Mark the current pending writes so later ones can be discarded.
+    pub fn savepoint(&mut self) -> SavepointId {
+        SavepointId(self.journal.len())
+    }
+
+    /// Discard all pending writes made after the savepoint (and any savepoint created after it).
+    pub fn rollback_to(&mut self, id: SavepointId) {
+        while self.journal.len() > id.0 {
+            let (key, previous) = self.journal.pop().unwrap();
+            match previous {
+                Some(op) => self.pending.insert(key, op),
+                None => self.pending.remove(&key),
+            };
+        }
+    }
+
+    /// Get the pending changes in the order they must be applied to the state: sorted by
+    /// [Key] (accounts by public key bytes, then the minter set), regardless of the order
+    /// they were written in. The state root depends on this order, so it must never change.
     pub fn commit(self) -> Vec<(Key, StateOperation)> {
         self.pending.into_iter().collect()
     }
@@ -176,55 +704,129 @@ where
     pub async fn execute(
         &mut self,
         txs: Vec<Transaction>
-    ) -> (BTreeMap<PublicKey, u64>, Vec<Transaction>) {
+    ) -> Result<(BTreeMap<PublicKey, u64>, Vec<Transaction>, Vec<ExecutionEvent>), StateError> {
         let mut processed_nonces = BTreeMap::new();
         let mut invalid_txs = Vec::new();
+        let mut events = Vec::new();
     
-        for tx in txs {
+        for tx in Self::order_transactions(txs) {
+            // Reject transactions signed for another chain
+            if tx.chain_id != self.state.chain_id {
+                invalid_txs.push(tx);
+                continue;
+            }
+
             // Must be applied in order to ensure blocks with multiple transactions from same
             // account are handled properly.
-            let sender= if let Some(account) = self.prepare_sender_account(&tx).await {
+            let mut sender= if let Some(account) = self.prepare_sender_account(&tx).await? {
                 account
             } else {
                 invalid_txs.push(tx);
                 continue;
             };
 
+            // Charge the maximum fee up front
+            let before_fee = self.savepoint();
+            let Some(max_fee) = tx.max_fee().filter(|fee| sender.can_afford(*fee)) else {
+                invalid_txs.push(tx);
+                continue;
+            };
+            sender.bread -= max_fee;
+            self.insert(Key::Account(tx.public_key.clone()), Value::Account(sender.clone()));
+
+            // Revert transactions running out of gas (the fee is still paid)
+            let gas_used = tx.instruction.gas_cost();
+            if gas_used > tx.gas_limit {
+                invalid_txs.push(tx.clone());
+                processed_nonces.insert(tx.public_key, tx.nonce.saturating_add(1));
+                continue;
+            }
+
             // Execute transaction
             let valid_tx = match tx.instruction.clone() {
                 Instruction::TransferBread(i) => 
-                    self.apply_transfer_bread(tx.public_key.clone(), &sender, &i).await,
+                    self.apply_transfer_bread(tx.public_key.clone(), &sender, &i).await?,
+                Instruction::MintBread(i) =>
+                    self.apply_mint_bread(tx.public_key.clone(), &sender, &i).await?,
+                Instruction::UpdateMinters(i) =>
+                    self.apply_update_minters(tx.public_key.clone(), &sender, &i),
+                Instruction::TransferBreadMemo(i) =>
+                    self.apply_transfer_bread(tx.public_key.clone(), &sender, &i.transfer()).await?,
+                // The sender nonce and fee were already updated
+                Instruction::Noop => true,
             };
             if !valid_tx {
+                self.rollback_to(before_fee);
                 invalid_txs.push(tx);
                 continue;
             }
 
+            // Refund unused gas
+            let refund = (tx.gas_limit - gas_used) * tx.gas_price;
+            if refund > 0 {
+                if let Some(Value::Account(mut account)) =
+                    self.get(&Key::Account(tx.public_key.clone())).await?
+                {
+                    account.bread += refund;
+                    self.insert(Key::Account(tx.public_key.clone()), Value::Account(account));
+                }
+            }
+
+            // Record the effect of the transaction
+            events.extend(ExecutionEvent::from_transaction(&tx));
+
             // Track the next nonce for this public key in case of valid transaction
             processed_nonces.insert(tx.public_key, tx.nonce.saturating_add(1));
         }
 
-        (processed_nonces, invalid_txs)
+        Ok((processed_nonces, invalid_txs, events))
+    }
+
+    /// Order transactions of each sender by nonce, keeping the positions occupied by
+    /// each sender (and thus the relative order of different senders) unchanged.
+    fn order_transactions(txs: Vec<Transaction>) -> Vec<Transaction> {
+        let mut senders = Vec::with_capacity(txs.len());
+        let mut pending = BTreeMap::<PublicKey, Vec<Transaction>>::new();
+        for tx in txs {
+            senders.push(tx.public_key.clone());
+            pending.entry(tx.public_key.clone()).or_default().push(tx);
+        }
+
+        // Sort in descending order so the lowest nonce can be popped first
+        for sender_txs in pending.values_mut() {
+            sender_txs.sort_by(|a, b| b.nonce.cmp(&a.nonce));
+        }
+
+        senders.into_iter()
+            .map(|sender| {
+                pending.get_mut(&sender)
+                    .and_then(|sender_txs| sender_txs.pop())
+                    .expect("missing sender transaction")
+            })
+            .collect()
     }
 
-    async fn prepare_sender_account(&mut self, tx: &Transaction) -> Option<Account> {
+    async fn prepare_sender_account(&mut self, tx: &Transaction) -> Result<Option<Account>, StateError> {
         // Get account
         let mut account = if let Some(Value::Account(account)) =
-            self.get(&Key::Account(tx.public_key.clone())).await
+            self.get(&Key::Account(tx.public_key.clone())).await?
         {
             account
         } else {
-           return None
+           return Ok(None)
         };
 
         // Ensure nonce is correct
-        if account.nonce != tx.nonce {
-            return None;
+        if !account.expects_nonce(tx.nonce) {
+            return Ok(None);
         }
-        // Increment nonce
-        account.nonce += 1;
+        // Increment nonce (an account at the last nonce can't send transactions anymore)
+        let Some(nonce) = account.nonce.checked_add(1) else {
+            return Ok(None);
+        };
+        account.nonce = nonce;
         
-        Some(account)
+        Ok(Some(account))
     }
 
     async fn apply_transfer_bread(
@@ -232,15 +834,20 @@ where
         sender_pk: PublicKey,
         sender: &Account,
         tx: &TransferBread
-    ) -> bool {
+    ) -> Result<bool, StateError> {
         // Check sender balance
-        if sender.bread < tx.amount {
-            return false
+        if !sender.can_afford(tx.amount) {
+            return Ok(false)
+        }
+
+        // Reject dust transfers (sweeping the whole balance is always allowed)
+        if tx.amount < self.state.min_transfer_amount && tx.amount != sender.bread {
+            return Ok(false)
         }
 
         // Create receiver acccount if necessary
         let mut receiver = if let Some(Value::Account(account)) =
-            self.get(&Key::Account(tx.to.clone())).await
+            self.get(&Key::Account(tx.to.clone())).await?
         {
             account
         } else {
@@ -256,23 +863,1022 @@ where
         receiver.bread += tx.amount;
         self.insert(Key::Account(tx.to.clone()), Value::Account(receiver));
     
+        Ok(true)
+    }
+
+    async fn apply_mint_bread(
+        &mut self,
+        sender_pk: PublicKey,
+        sender: &Account,
+        tx: &MintBread
+    ) -> Result<bool, StateError> {
+        // Check sender is an authorized minter
+        let minters = if let Some(Value::MinterSet(minters)) =
+            self.get(&Key::MinterSet).await?
+        {
+            minters
+        } else {
+            Vec::new()
+        };
+        if !minters.contains(&sender_pk) {
+            return Ok(false)
+        }
+
+        // Create receiver acccount if necessary
+        let mut receiver = if tx.to == sender_pk {
+            sender.clone()
+        } else if let Some(Value::Account(account)) =
+            self.get(&Key::Account(tx.to.clone())).await?
+        {
+            account
+        } else {
+            Account::default()
+        };
+        let Some(bread) = receiver.bread.checked_add(tx.amount) else {
+            return Ok(false)
+        };
+        receiver.bread = bread;
+
+        // Update sender nonce and receiver balance
+        self.insert(Key::Account(sender_pk), Value::Account(sender.clone()));
+        self.insert(Key::Account(tx.to.clone()), Value::Account(receiver));
+
+        Ok(true)
+    }
+
+    fn apply_update_minters(
+        &mut self,
+        sender_pk: PublicKey,
+        sender: &Account,
+        tx: &UpdateMinters
+    ) -> bool {
+        // Only governance can update minters
+        if sender_pk != self.state.governance_key {
+            return false
+        }
+
+        // Update sender nonce and minter set
+        self.insert(Key::Account(sender_pk), Value::Account(sender.clone()));
+        self.insert(Key::MinterSet, Value::MinterSet(tx.minters.clone()));
+
         true
     }
 
     fn insert(&mut self, key: Key, value: Value) {
-        self.pending.insert(key, StateOperation::Update(value));
+        let previous = self.pending.insert(key.clone(), StateOperation::Update(value));
+        self.journal.push((key, previous));
     }
 
     fn delete(&mut self, key: Key) {
-        self.pending.insert(key, StateOperation::Delete);
+        let previous = self.pending.insert(key.clone(), StateOperation::Delete);
+        self.journal.push((key, previous));
     }
 
-    async fn get(&self, key: &Key) -> Option<Value> {
+    async fn get(&self, key: &Key) -> Result<Option<Value>, StateError> {
         match self.pending.get(key) {
-            Some(StateOperation::Update(value)) => Some(value.clone()),
-            Some(StateOperation::Delete) => None,
+            Some(StateOperation::Update(value)) => Ok(Some(value.clone())),
+            Some(StateOperation::Delete) => Ok(None),
             None => self.state.get(key).await,
         }
     }
 
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        future::Future,
+        num::NonZeroUsize,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::{Duration, SystemTime},
+    };
+
+    use commonware_codec::EncodeSize;
+    use commonware_cryptography::{ed25519::PrivateKey, PrivateKeyExt, Signer};
+    use commonware_runtime::{
+        buffer::PoolRef, deterministic, signal::Signal, Error as RuntimeError, Handle, Runner,
+    };
+    use commonware_storage::{adb::any::variable::Config as AdbConfig, translator::EightCap};
+    use prometheus_client::registry::Metric;
+
+    use crate::types::{TransferBreadMemo, BASE_GAS, MAX_MEMO_SIZE};
+
+    const CHAIN_ID: u64 = 1;
+
+    fn hash(n: u8) -> Digest {
+        [n; 32].into()
+    }
+
+    fn config(governance_key: PublicKey) -> Config {
+        Config {
+            chain_id: CHAIN_ID,
+            genesis_block_hash: hash(0),
+            governance_key,
+            min_transfer_amount: 0,
+            max_height: None,
+            event_sender: None,
+        }
+    }
+
+    async fn init_adb<E>(context: E, partition: &str) -> Any<E, Digest, Value, Sha256, EightCap>
+    where
+        E: Spawner + Metrics + Clock + Storage,
+    {
+        Any::init(context, AdbConfig {
+            mmr_journal_partition: format!("{partition}_mmr_journal"),
+            mmr_metadata_partition: format!("{partition}_mmr_metadata"),
+            mmr_items_per_blob: NonZeroU64::new(7).unwrap(),
+            mmr_write_buffer: NonZeroUsize::new(1024).unwrap(),
+            log_journal_partition: format!("{partition}_log_journal"),
+            log_items_per_section: NonZeroU64::new(7).unwrap(),
+            log_write_buffer: NonZeroUsize::new(1024).unwrap(),
+            log_compression: None,
+            log_codec_config: (),
+            locations_journal_partition: format!("{partition}_locations_journal"),
+            locations_items_per_blob: NonZeroU64::new(7).unwrap(),
+            translator: EightCap,
+            thread_pool: None,
+            buffer_pool: PoolRef::new(NonZeroUsize::new(1024).unwrap(), NonZeroUsize::new(16).unwrap()),
+        })
+        .await
+        .expect("failed to initialize adb")
+    }
+
+    /// Create a state holding the given accounts (committed at height 0).
+    async fn init_state<E>(context: E, config: Config, accounts: &[(PublicKey, u64)]) -> State<E, EightCap>
+    where
+        E: Spawner + Metrics + Clock + Storage,
+    {
+        let adb = init_adb(context.with_label("adb"), "state").await;
+        let mut state = State::new(context.with_label("state"), adb, config);
+        let changes = accounts.iter()
+            .map(|(public, bread)| (
+                Key::Account(public.clone()),
+                StateOperation::Update(Value::Account(Account { nonce: 0, bread: *bread })),
+            ))
+            .collect();
+        state.apply(changes, CommitMetadata { height: 0, start: 0, head: hash(0) }).await.unwrap();
+        state
+    }
+
+    fn transfer(signer: &PrivateKey, nonce: u64, to: &PublicKey, amount: u64) -> Transaction {
+        let instruction = Instruction::TransferBread(TransferBread { amount, to: to.clone() });
+        Transaction::sign(signer, CHAIN_ID, nonce, BASE_GAS, 0, instruction)
+    }
+
+    /// A context whose storage fails to open blobs once `fail` is set.
+    #[derive(Clone)]
+    struct FailingStorage {
+        context: deterministic::Context,
+        fail: Arc<AtomicBool>,
+    }
+
+    impl Storage for FailingStorage {
+        type Blob = <deterministic::Context as Storage>::Blob;
+
+        async fn open(&self, partition: &str, name: &[u8]) -> Result<(Self::Blob, u64), RuntimeError> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(RuntimeError::PartitionMissing(partition.to_string()));
+            }
+            self.context.open(partition, name).await
+        }
+
+        async fn remove(&self, partition: &str, name: Option<&[u8]>) -> Result<(), RuntimeError> {
+            self.context.remove(partition, name).await
+        }
+
+        async fn scan(&self, partition: &str) -> Result<Vec<Vec<u8>>, RuntimeError> {
+            self.context.scan(partition).await
+        }
+    }
+
+    impl Clock for FailingStorage {
+        fn current(&self) -> SystemTime {
+            self.context.current()
+        }
+
+        fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send + 'static {
+            self.context.sleep(duration)
+        }
+
+        fn sleep_until(&self, deadline: SystemTime) -> impl Future<Output = ()> + Send + 'static {
+            self.context.sleep_until(deadline)
+        }
+    }
+
+    impl Metrics for FailingStorage {
+        fn label(&self) -> String {
+            self.context.label()
+        }
+
+        fn with_label(&self, label: &str) -> Self {
+            Self {
+                context: self.context.with_label(label),
+                fail: self.fail.clone(),
+            }
+        }
+
+        fn register<N: Into<String>, H: Into<String>>(&self, name: N, help: H, metric: impl Metric) {
+            self.context.register(name, help, metric)
+        }
+
+        fn encode(&self) -> String {
+            self.context.encode()
+        }
+    }
+
+    impl Spawner for FailingStorage {
+        fn spawn<F, Fut, T>(self, f: F) -> Handle<T>
+        where
+            F: FnOnce(Self) -> Fut + Send + 'static,
+            Fut: Future<Output = T> + Send + 'static,
+            T: Send + 'static,
+        {
+            let fail = self.fail;
+            self.context.spawn(move |context| f(Self { context, fail }))
+        }
+
+        fn spawn_ref<F, T>(&mut self) -> impl FnOnce(F) -> Handle<T> + 'static
+        where
+            F: Future<Output = T> + Send + 'static,
+            T: Send + 'static,
+        {
+            self.context.spawn_ref()
+        }
+
+        fn spawn_blocking<F, T>(self, dedicated: bool, f: F) -> Handle<T>
+        where
+            F: FnOnce(Self) -> T + Send + 'static,
+            T: Send + 'static,
+        {
+            let fail = self.fail;
+            self.context.spawn_blocking(dedicated, move |context| f(Self { context, fail }))
+        }
+
+        fn spawn_blocking_ref<F, T>(&mut self, dedicated: bool) -> impl FnOnce(F) -> Handle<T> + 'static
+        where
+            F: FnOnce() -> T + Send + 'static,
+            T: Send + 'static,
+        {
+            self.context.spawn_blocking_ref(dedicated)
+        }
+
+        fn stop(self, value: i32, timeout: Option<Duration>) -> impl Future<Output = Result<(), RuntimeError>> + Send {
+            self.context.stop(value, timeout)
+        }
+
+        fn stopped(&self) -> Signal {
+            self.context.stopped()
+        }
+    }
+
+    /// Sign a transaction with enough gas for any instruction (and no fee).
+    fn sign(signer: &PrivateKey, nonce: u64, instruction: Instruction) -> Transaction {
+        Transaction::sign(signer, CHAIN_ID, nonce, 100 * BASE_GAS, 0, instruction)
+    }
+
+    #[test]
+    fn test_import_block() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let mut state = init_state(context, config(alice.public_key()), &[(alice.public_key(), 100)]).await;
+
+            // Import a block extending the state head
+            let block = Block::new(hash(0), 1, vec![transfer(&alice, 0, &bob, 40)]);
+            let result = import_block(&mut state, &block, None).await.unwrap();
+            assert!(result.applied);
+            assert!(result.invalid_txs.is_empty());
+            assert_eq!(state.balance(&bob).await.unwrap(), 40);
+            assert_eq!(state.commit_metadata().await.unwrap().head, block.digest());
+
+            // Reject a block with another parent
+            let block = Block::new(hash(9), 2, vec![transfer(&alice, 1, &bob, 10)]);
+            assert!(matches!(
+                import_block(&mut state, &block, None).await,
+                Err(ImportError::ParentMismatch(parent)) if parent == hash(9)
+            ));
+            assert_eq!(state.balance(&bob).await.unwrap(), 40);
+        });
+    }
+
+    #[test]
+    fn test_shuffled_nonces() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let mut state = init_state(context, config(alice.public_key()), &[(alice.public_key(), 100)]).await;
+
+            // Transactions of the same sender are applied by nonce
+            let txs = vec![
+                transfer(&alice, 2, &bob, 1),
+                transfer(&alice, 0, &bob, 1),
+                transfer(&alice, 1, &bob, 1),
+            ];
+            let result = execute_state_transition(&mut state, txs, 1, hash(1)).await.unwrap();
+            assert!(result.invalid_txs.is_empty());
+            assert_eq!(result.processed_nonces[&alice.public_key()], 3);
+            assert_eq!(state.balance(&bob).await.unwrap(), 3);
+        });
+    }
+
+    #[test]
+    fn test_invalid_transactions_metric() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1);
+            let mut state = init_state(context, config(alice.public_key()), &[(alice.public_key(), 100)]).await;
+
+            // Reject an unknown sender and a wrong nonce
+            let txs = vec![
+                transfer(&bob, 0, &alice.public_key(), 1),
+                transfer(&alice, 0, &bob.public_key(), 1),
+                transfer(&alice, 5, &bob.public_key(), 1),
+            ];
+            let result = execute_state_transition(&mut state, txs, 1, hash(1)).await.unwrap();
+            assert_eq!(result.invalid_txs.len(), 2);
+            assert_eq!(state.invalid_transactions.get(), 2);
+
+            // The counter accumulates across transitions
+            let txs = vec![transfer(&alice, 0, &bob.public_key(), 1)];
+            execute_state_transition(&mut state, txs, 2, hash(2)).await.unwrap();
+            assert_eq!(state.invalid_transactions.get(), 3);
+        });
+    }
+
+    #[test]
+    fn test_storage_error() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let fail = Arc::new(AtomicBool::new(false));
+            let context = FailingStorage { context, fail: fail.clone() };
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let mut state = init_state(context, config(alice.public_key()), &[(alice.public_key(), 100)]).await;
+
+            // Storage errors surface once the adb needs a new blob
+            fail.store(true, Ordering::SeqCst);
+            let mut failed = false;
+            for height in 1..=16u64 {
+                let txs = vec![transfer(&alice, height - 1, &bob, 1)];
+                if execute_state_transition(&mut state, txs, height, hash(height as u8)).await.is_err() {
+                    failed = true;
+                    break;
+                }
+            }
+            assert!(failed);
+        });
+    }
+
+    #[test]
+    fn test_get_at() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let mut state = init_state(context, config(alice.public_key()), &[(alice.public_key(), 100)]).await;
+            for (height, amount) in [(1u64, 10), (2, 20), (3, 5)] {
+                let txs = vec![transfer(&alice, height - 1, &bob, amount)];
+                execute_state_transition(&mut state, txs, height, hash(height as u8)).await.unwrap();
+            }
+
+            // Read the balance as of each height
+            let key = Key::Account(bob.clone());
+            assert_eq!(
+                state.get_at(&key, 2).await.unwrap(),
+                Some(Value::Account(Account { nonce: 0, bread: 30 })),
+            );
+            assert_eq!(
+                state.get_at(&key, 3).await.unwrap(),
+                Some(Value::Account(Account { nonce: 0, bread: 35 })),
+            );
+            assert_eq!(state.get_at(&key, 0).await.unwrap(), None);
+            assert!(matches!(state.get_at(&key, 4).await, Err(StateError::HistoryUnavailable(4))));
+        });
+    }
+
+    #[test]
+    fn test_simulate_transactions() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let state = init_state(context, config(alice.public_key()), &[(alice.public_key(), 100)]).await;
+            let mut hasher = Standard::<Sha256>::new();
+            let root = state.root(&mut hasher);
+            let operations = state.operation_count();
+
+            // Simulate a transfer
+            let result = simulate_transactions(&state, vec![transfer(&alice, 0, &bob, 40)]).await.unwrap();
+            assert!(result.invalid_txs.is_empty());
+            assert_eq!(result.processed_nonces[&alice.public_key()], 1);
+            assert_eq!(result.changes.len(), 2);
+
+            // The state is unchanged
+            assert_eq!(state.root(&mut hasher), root);
+            assert_eq!(state.operation_count(), operations);
+            assert_eq!(state.balance(&alice.public_key()).await.unwrap(), 100);
+            assert_eq!(state.balance(&bob).await.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn test_update_minters() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let governance = PrivateKey::from_seed(0);
+            let (first, second) = (PrivateKey::from_seed(1), PrivateKey::from_seed(2));
+            let bob = PrivateKey::from_seed(3).public_key();
+            let accounts = [
+                (governance.public_key(), 0),
+                (first.public_key(), 0),
+                (second.public_key(), 0),
+            ];
+            let mut state = init_state(context, config(governance.public_key()), &accounts).await;
+            let mint = |signer: &PrivateKey, nonce, amount| {
+                sign(signer, nonce, Instruction::MintBread(MintBread { amount, to: bob.clone() }))
+            };
+            let update = |nonce, minters: Vec<PublicKey>| {
+                sign(&governance, nonce, Instruction::UpdateMinters(UpdateMinters { minters }))
+            };
+
+            // Only minters in the set can mint
+            let txs = vec![update(0, vec![first.public_key()]), mint(&first, 0, 10), mint(&second, 0, 10)];
+            let result = execute_state_transition(&mut state, txs, 1, hash(1)).await.unwrap();
+            assert_eq!(result.invalid_txs.len(), 1);
+            assert_eq!(result.invalid_txs[0].public_key, second.public_key());
+            assert_eq!(state.balance(&bob).await.unwrap(), 10);
+
+            // Replace the minter set
+            let txs = vec![update(1, vec![second.public_key()]), mint(&first, 1, 10), mint(&second, 0, 5)];
+            let result = execute_state_transition(&mut state, txs, 2, hash(2)).await.unwrap();
+            assert_eq!(result.invalid_txs.len(), 1);
+            assert_eq!(result.invalid_txs[0].public_key, first.public_key());
+            assert_eq!(state.balance(&bob).await.unwrap(), 15);
+
+            // Only governance can update the set (rejected transactions don't consume nonces)
+            let txs = vec![sign(&first, 1, Instruction::UpdateMinters(UpdateMinters { minters: vec![] }))];
+            let result = execute_state_transition(&mut state, txs, 3, hash(3)).await.unwrap();
+            assert_eq!(result.invalid_txs.len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_root_at() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let mut state = init_state(context, config(alice.public_key()), &[(alice.public_key(), 100)]).await;
+            let mut hasher = Standard::<Sha256>::new();
+            let mut roots = Vec::new();
+            for height in 1..=3u64 {
+                let txs = vec![transfer(&alice, height - 1, &bob, 1)];
+                execute_state_transition(&mut state, txs, height, hash(height as u8)).await.unwrap();
+                roots.push(state.root(&mut hasher));
+            }
+
+            // Read back the root of each height
+            for (height, root) in (1..=3u64).zip(&roots) {
+                assert_eq!(state.root_at(height).await.unwrap(), Some(*root));
+            }
+            assert_ne!(roots[0], roots[1]);
+            assert_ne!(roots[1], roots[2]);
+            assert_eq!(state.root_at(4).await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn test_prevalidate_block() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let state = init_state(context, config(alice.public_key()), &[(alice.public_key(), 100)]).await;
+
+            // Reject a block of mostly unknown senders
+            let mut txs = vec![transfer(&alice, 0, &bob, 1)];
+            txs.extend((10..19).map(|seed| transfer(&PrivateKey::from_seed(seed), 0, &bob, 1)));
+            let block = Block::new(hash(0), 1, txs);
+            assert!(matches!(
+                prevalidate_block(&state, &block, 0.5).await,
+                Err(PrevalidationError::TooManyInvalid(9))
+            ));
+
+            // Accept a block with few invalid transactions (here a wrong nonce)
+            let mut txs = (0..9).map(|nonce| transfer(&alice, nonce, &bob, 1)).collect::<Vec<_>>();
+            txs.push(transfer(&alice, 20, &bob, 1));
+            let block = Block::new(hash(0), 1, txs);
+            assert!(prevalidate_block(&state, &block, 0.5).await.is_ok());
+        });
+    }
+
+    #[test]
+    fn test_savepoint_rollback() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0).public_key();
+            let bob = PrivateKey::from_seed(1).public_key();
+            let state = init_state(context, config(alice.clone()), &[(alice.clone(), 100)]).await;
+            let account = |bread| Value::Account(Account { nonce: 0, bread });
+
+            // Discard the writes made after the savepoint
+            let mut layer = StateLayer::new(&state);
+            layer.insert(Key::Account(alice.clone()), account(1));
+            let savepoint = layer.savepoint();
+            layer.insert(Key::Account(alice.clone()), account(2));
+            layer.insert(Key::Account(bob.clone()), account(3));
+            layer.delete(Key::MinterSet);
+            layer.rollback_to(savepoint);
+
+            let changes = layer.commit();
+            assert_eq!(changes.len(), 1);
+            assert!(matches!(
+                &changes[0],
+                (Key::Account(public), StateOperation::Update(Value::Account(Account { bread: 1, .. })))
+                    if *public == alice
+            ));
+        });
+    }
+
+    #[test]
+    fn test_import_block_too_large() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let mut state = init_state(context, config(alice.public_key()), &[(alice.public_key(), 100)]).await;
+            let memo = |nonce| sign(&alice, nonce, Instruction::TransferBreadMemo(TransferBreadMemo {
+                amount: 1,
+                to: bob.clone(),
+                memo: vec![0; MAX_MEMO_SIZE],
+            }));
+            let block = Block::new(hash(0), 1, vec![memo(0), memo(1)]);
+            let size = block.size_bytes();
+            assert_eq!(size, block.encode_size());
+
+            // Reject a block above the byte limit (with few transactions)
+            assert!(matches!(
+                import_block(&mut state, &block, Some(size - 1)).await,
+                Err(ImportError::TooLarge(len)) if len == size
+            ));
+            assert_eq!(state.balance(&bob).await.unwrap(), 0);
+
+            // Accept it at the limit
+            let result = import_block(&mut state, &block, Some(size)).await.unwrap();
+            assert!(result.invalid_txs.is_empty());
+            assert_eq!(state.balance(&bob).await.unwrap(), 2);
+        });
+    }
+
+    #[test]
+    fn test_gas() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let mut state = init_state(context, config(alice.public_key()), &[(alice.public_key(), 1_000)]).await;
+
+            // Refund unused gas
+            let instruction = Instruction::TransferBread(TransferBread { amount: 10, to: bob.clone() });
+            let tx = Transaction::sign(&alice, CHAIN_ID, 0, 2 * BASE_GAS, 1, instruction);
+            let result = execute_state_transition(&mut state, vec![tx], 1, hash(1)).await.unwrap();
+            assert!(result.invalid_txs.is_empty());
+            assert_eq!(state.balance(&bob).await.unwrap(), 10);
+            assert_eq!(state.balance(&alice.public_key()).await.unwrap(), 1_000 - 10 - BASE_GAS);
+
+            // Revert transactions running out of gas (still paying the whole limit)
+            let instruction = Instruction::TransferBreadMemo(TransferBreadMemo {
+                amount: 10,
+                to: bob.clone(),
+                memo: vec![0; 10],
+            });
+            let tx = Transaction::sign(&alice, CHAIN_ID, 1, BASE_GAS, 1, instruction);
+            let result = execute_state_transition(&mut state, vec![tx], 2, hash(2)).await.unwrap();
+            assert_eq!(result.invalid_txs.len(), 1);
+            assert_eq!(result.processed_nonces[&alice.public_key()], 2);
+            assert_eq!(state.balance(&bob).await.unwrap(), 10);
+            assert_eq!(state.balance(&alice.public_key()).await.unwrap(), 1_000 - 10 - 2 * BASE_GAS);
+
+            // Reject transactions whose maximum fee can't be paid (without consuming the nonce)
+            let tx = Transaction::sign(&alice, CHAIN_ID, 2, 1_000 * BASE_GAS, 1, Instruction::Noop);
+            let result = execute_state_transition(&mut state, vec![tx], 3, hash(3)).await.unwrap();
+            assert_eq!(result.invalid_txs.len(), 1);
+            assert_eq!(state.processed_nonce(&alice.public_key()).await.unwrap(), 2);
+        });
+    }
+
+    #[test]
+    fn test_account() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0).public_key();
+            let bob = PrivateKey::from_seed(1).public_key();
+            let carol = PrivateKey::from_seed(2).public_key();
+            let mut state = init_state(context, config(alice.clone()), &[(alice.clone(), 100)]).await;
+
+            // Funded account
+            assert_eq!(state.account(&alice).await.unwrap(), Account { nonce: 0, bread: 100 });
+            assert_eq!(state.balance(&alice).await.unwrap(), 100);
+
+            // Unknown account
+            assert_eq!(state.account(&bob).await.unwrap(), Account::default());
+            assert_eq!(state.balance(&bob).await.unwrap(), 0);
+
+            // Other values are not mistaken for accounts
+            let metadata = CommitMetadata { height: 7, start: 0, head: hash(7) };
+            let changes = vec![(
+                Key::Account(carol.clone()),
+                StateOperation::Update(Value::CommitMetadata(metadata)),
+            )];
+            let start = state.operation_count();
+            state.apply(changes, CommitMetadata { height: 1, start, head: hash(1) }).await.unwrap();
+            assert_eq!(state.account(&carol).await.unwrap(), Account::default());
+            assert_eq!(state.commit_metadata().await.unwrap().height, 1);
+        });
+    }
+
+    #[test]
+    fn test_verify_block_signatures() {
+        let alice = PrivateKey::from_seed(0);
+        let bob = PrivateKey::from_seed(1).public_key();
+
+        // Full valid block
+        let txs = (0..MAX_BLOCK_TRANSACTIONS as u64)
+            .map(|nonce| transfer(&alice, nonce, &bob, 1))
+            .collect::<Vec<_>>();
+        let block = Block::new(hash(0), 1, txs.clone());
+        assert_eq!(verify_block_signatures(&block), Ok(()));
+
+        // Report the transaction with a bad signature
+        let mut txs = txs;
+        txs[4].signature = txs[5].signature.clone();
+        let block = Block::new(hash(0), 1, txs);
+        assert_eq!(verify_block_signatures(&block), Err(vec![4]));
+    }
+
+    #[test]
+    fn test_min_transfer_amount() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1);
+            let carol = PrivateKey::from_seed(2).public_key();
+            let mut config = config(alice.public_key());
+            config.min_transfer_amount = 10;
+            let accounts = [(alice.public_key(), 100), (bob.public_key(), 5)];
+            let mut state = init_state(context, config, &accounts).await;
+
+            // Reject dust transfers
+            let txs = vec![transfer(&alice, 0, &carol, 1)];
+            let result = execute_state_transition(&mut state, txs, 1, hash(1)).await.unwrap();
+            assert_eq!(result.invalid_txs.len(), 1);
+            assert_eq!(state.balance(&carol).await.unwrap(), 0);
+
+            // Accept larger transfers (rejected transactions don't consume nonces)
+            let txs = vec![transfer(&alice, 0, &carol, 20)];
+            let result = execute_state_transition(&mut state, txs, 2, hash(2)).await.unwrap();
+            assert!(result.invalid_txs.is_empty());
+            assert_eq!(state.balance(&carol).await.unwrap(), 20);
+
+            // Sweeping a whole balance below the minimum is allowed
+            let txs = vec![transfer(&bob, 0, &carol, 5)];
+            let result = execute_state_transition(&mut state, txs, 3, hash(3)).await.unwrap();
+            assert!(result.invalid_txs.is_empty());
+            assert_eq!(state.balance(&bob.public_key()).await.unwrap(), 0);
+            assert_eq!(state.balance(&carol).await.unwrap(), 25);
+        });
+    }
+
+    #[test]
+    fn test_max_height() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let mut config = config(alice.public_key());
+            config.max_height = Some(1);
+            let mut state = init_state(context, config, &[(alice.public_key(), 100)]).await;
+
+            // Accept transitions up to the maximum height
+            let txs = vec![transfer(&alice, 0, &bob, 1)];
+            execute_state_transition(&mut state, txs, 1, hash(1)).await.unwrap();
+
+            // Reject the next one without changing the state
+            let txs = vec![transfer(&alice, 1, &bob, 1)];
+            assert!(matches!(
+                execute_state_transition(&mut state, txs, 2, hash(2)).await,
+                Err(StateError::HeightLimit(2))
+            ));
+            assert_eq!(state.commit_metadata().await.unwrap().height, 1);
+            assert_eq!(state.balance(&bob).await.unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn test_prune() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let mut state = init_state(context, config(alice.public_key()), &[(alice.public_key(), 100)]).await;
+            for height in 1..=30u64 {
+                let txs = vec![transfer(&alice, height - 1, &bob, 1)];
+                execute_state_transition(&mut state, txs, height, hash(height as u8)).await.unwrap();
+            }
+            let operations = state.operation_count();
+
+            // Prune all history
+            state.prune(0).await.unwrap();
+            assert_eq!(state.operation_count(), operations);
+
+            // The current state is still readable
+            assert_eq!(state.balance(&alice.public_key()).await.unwrap(), 70);
+            assert_eq!(state.balance(&bob).await.unwrap(), 30);
+            assert_eq!(state.commit_metadata().await.unwrap().height, 30);
+            assert_eq!(
+                state.get_at(&Key::Account(bob.clone()), 30).await.unwrap(),
+                Some(Value::Account(Account { nonce: 0, bread: 30 })),
+            );
+
+            // Pruned heights are not
+            assert!(matches!(
+                state.get_at(&Key::Account(bob), 1).await,
+                Err(StateError::HistoryUnavailable(1))
+            ));
+        });
+    }
+
+    #[test]
+    fn test_chain_id() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let mut config = config(alice.public_key());
+            config.chain_id = 2;
+            let mut state = init_state(context, config, &[(alice.public_key(), 100)]).await;
+
+            // Reject transactions signed for another chain
+            let result = execute_state_transition(&mut state, vec![transfer(&alice, 0, &bob, 1)], 1, hash(1)).await.unwrap();
+            assert_eq!(result.invalid_txs.len(), 1);
+            assert_eq!(state.balance(&bob).await.unwrap(), 0);
+
+            // Accept them once signed for this chain
+            let instruction = Instruction::TransferBread(TransferBread { amount: 1, to: bob.clone() });
+            let tx = Transaction::sign(&alice, 2, 0, BASE_GAS, 0, instruction);
+            let result = execute_state_transition(&mut state, vec![tx], 2, hash(2)).await.unwrap();
+            assert!(result.invalid_txs.is_empty());
+            assert_eq!(state.balance(&bob).await.unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn test_tip_transition() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let mut state = init_state(context, config(alice.public_key()), &[(alice.public_key(), 100)]).await;
+            let txs = vec![transfer(&alice, 0, &bob, 1)];
+            let applied = execute_state_transition(&mut state, txs.clone(), 1, hash(1)).await.unwrap();
+            assert!(applied.applied);
+
+            // Re-executing the tip is a no-op
+            let result = execute_state_transition(&mut state, txs, 1, hash(1)).await.unwrap();
+            assert!(!result.applied);
+            assert_eq!(result.state_root, applied.state_root);
+            assert_eq!(result.state_start_op, applied.state_start_op);
+            assert_eq!(result.state_end_op, applied.state_end_op);
+            assert!(result.processed_nonces.is_empty());
+            assert_eq!(state.balance(&bob).await.unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn test_import_block_after_restart() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let mut state = init_state(context.clone(), config(alice.public_key()), &[(alice.public_key(), 100)]).await;
+            let block = Block::new(hash(0), 1, vec![transfer(&alice, 0, &bob, 1)]);
+            import_block(&mut state, &block, None).await.unwrap();
+            drop(state);
+
+            // Resume from the committed head
+            let adb = init_adb(context.with_label("restarted_adb"), "state").await;
+            let mut state = State::new(context.with_label("restarted_state"), adb, config(alice.public_key()));
+            let metadata = state.commit_metadata().await.unwrap();
+            assert_eq!((metadata.height, metadata.head), (1, block.digest()));
+
+            // Reject blocks at the wrong height or with the wrong parent
+            let next = Block::new(hash(9), 2, vec![transfer(&alice, 1, &bob, 1)]);
+            assert!(matches!(
+                import_block(&mut state, &next, None).await,
+                Err(ImportError::ParentMismatch(parent)) if parent == hash(9)
+            ));
+            let next = Block::new(block.digest(), 3, vec![transfer(&alice, 1, &bob, 1)]);
+            assert!(matches!(
+                import_block(&mut state, &next, None).await,
+                Err(ImportError::InvalidBlockHeight(3))
+            ));
+
+            // Accept the next block
+            let next = Block::new(block.digest(), 2, vec![transfer(&alice, 1, &bob, 1)]);
+            import_block(&mut state, &next, None).await.unwrap();
+            assert_eq!(state.balance(&bob).await.unwrap(), 2);
+        });
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let mut state = init_state(context.clone(), config(alice.public_key()), &[(alice.public_key(), 100)]).await;
+            let block = Block::new(hash(0), 1, vec![transfer(&alice, 0, &bob, 10)]);
+            import_block(&mut state, &block, None).await.unwrap();
+
+            // Export the current values (proven against the root)
+            let snapshot = state.export_snapshot().await.unwrap();
+            let mut hasher = Standard::<Sha256>::new();
+            assert_eq!(snapshot.root, state.root(&mut hasher));
+            let (metadata, _) = snapshot.verify().unwrap();
+            assert_eq!((metadata.height, metadata.head), (1, block.digest()));
+
+            // Import them into a fresh state
+            let adb = init_adb(context.with_label("imported_adb"), "imported").await;
+            let mut imported = State::import_snapshot(
+                context.with_label("imported_state"),
+                adb,
+                config(alice.public_key()),
+                snapshot.clone(),
+            ).await.unwrap();
+            assert_eq!(imported.account(&alice.public_key()).await.unwrap(), Account { nonce: 1, bread: 90 });
+            assert_eq!(imported.balance(&bob).await.unwrap(), 10);
+            let metadata = imported.commit_metadata().await.unwrap();
+            assert_eq!((metadata.height, metadata.head), (1, block.digest()));
+
+            // The imported state continues from the snapshot head
+            let next = Block::new(block.digest(), 2, vec![transfer(&alice, 1, &bob, 5)]);
+            import_block(&mut imported, &next, None).await.unwrap();
+            assert_eq!(imported.balance(&bob).await.unwrap(), 15);
+
+            // Reject a snapshot whose operations don't match its root
+            let mut tampered = snapshot;
+            for op in tampered.operations.iter_mut() {
+                if let Operation::Update(_, value @ Value::Account(_)) = op {
+                    *value = Value::Account(Account { nonce: 0, bread: 1000 });
+                }
+            }
+            assert!(tampered.verify().is_none());
+            let adb = init_adb(context.with_label("tampered_adb"), "tampered").await;
+            assert!(matches!(
+                State::import_snapshot(context.with_label("tampered_state"), adb, config(alice.public_key()), tampered).await,
+                Err(StateError::InvalidSnapshot)
+            ));
+        });
+    }
+
+    #[test]
+    fn test_last_nonce() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let adb = init_adb(context.with_label("adb"), "state").await;
+            let mut state = State::new(context.with_label("state"), adb, config(alice.public_key()));
+            let account = Account { nonce: u64::MAX, bread: 1_000 };
+            let changes = vec![(
+                Key::Account(alice.public_key()),
+                StateOperation::Update(Value::Account(account.clone())),
+            )];
+            state.apply(changes, CommitMetadata { height: 0, start: 0, head: hash(0) }).await.unwrap();
+
+            // Reject transactions at the last nonce (without charging them)
+            let tx = Transaction::sign(&alice, CHAIN_ID, u64::MAX, BASE_GAS, 1, Instruction::Noop);
+            let result = execute_state_transition(&mut state, vec![tx], 1, hash(1)).await.unwrap();
+            assert_eq!(result.invalid_txs.len(), 1);
+            assert_eq!(state.account(&alice.public_key()).await.unwrap(), account);
+        });
+    }
+
+    #[test]
+    fn test_apply_keys() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0).public_key();
+            let bob = PrivateKey::from_seed(1).public_key();
+            let carol = PrivateKey::from_seed(2).public_key();
+            let mut state = init_state(context, config(alice.clone()), &[(alice.clone(), 100), (bob.clone(), 50)]).await;
+
+            // Report every updated or deleted key (in order)
+            let changes = vec![
+                (Key::Account(alice.clone()), StateOperation::Update(Value::Account(Account { nonce: 1, bread: 90 }))),
+                (Key::Account(carol.clone()), StateOperation::Update(Value::Account(Account { nonce: 0, bread: 10 }))),
+                (Key::Account(bob.clone()), StateOperation::Delete),
+            ];
+            let start = state.operation_count();
+            let keys = state.apply(changes, CommitMetadata { height: 1, start, head: hash(1) }).await.unwrap();
+            assert_eq!(keys, vec![Key::Account(alice.clone()), Key::Account(carol.clone()), Key::Account(bob.clone())]);
+
+            assert_eq!(state.balance(&alice).await.unwrap(), 90);
+            assert_eq!(state.balance(&carol).await.unwrap(), 10);
+            assert_eq!(state.get(&Key::Account(bob)).await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn test_event_sender() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let bob = PrivateKey::from_seed(1).public_key();
+            let (sender, mut receiver) = mpsc::unbounded();
+            let config = Config { event_sender: Some(sender), ..config(alice.public_key()) };
+            let mut state = init_state(context, config, &[(alice.public_key(), 100)]).await;
+
+            // Push the events of the transition (as returned)
+            let result = execute_state_transition(&mut state, vec![transfer(&alice, 0, &bob, 10)], 1, hash(1)).await.unwrap();
+            let expected = ExecutionEvent::Transferred(Transfer { from: alice.public_key(), to: bob.clone(), amount: 10 });
+            assert_eq!(result.events, vec![expected.clone()]);
+            assert_eq!(receiver.try_next().unwrap(), Some(expected));
+
+            // Invalid transactions and re-executed tips emit nothing
+            execute_state_transition(&mut state, vec![transfer(&alice, 0, &bob, 10)], 2, hash(2)).await.unwrap();
+            execute_state_transition(&mut state, vec![transfer(&alice, 1, &bob, 10)], 2, hash(2)).await.unwrap();
+            assert!(receiver.try_next().is_err());
+        });
+    }
+
+    #[test]
+    fn test_commit_order() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let keys = (0..4).map(|seed| PrivateKey::from_seed(seed).public_key()).collect::<Vec<_>>();
+            let state = init_state(context, config(keys[0].clone()), &[]).await;
+            let account = |bread| Value::Account(Account { nonce: 0, bread });
+
+            // Write the same changes in opposite orders
+            let mut forward = StateLayer::new(&state);
+            for (bread, public) in keys.iter().enumerate() {
+                forward.insert(Key::Account(public.clone()), account(bread as u64));
+            }
+            forward.delete(Key::MinterSet);
+            let mut backward = StateLayer::new(&state);
+            backward.delete(Key::MinterSet);
+            for (bread, public) in keys.iter().enumerate().rev() {
+                backward.insert(Key::Account(public.clone()), account(bread as u64));
+            }
+
+            // Commit accounts by public key bytes, then the minter set
+            let mut expected = keys.iter()
+                .enumerate()
+                .map(|(bread, public)| (public.as_ref().to_vec(), bread as u64))
+                .collect::<Vec<_>>();
+            expected.sort();
+            for changes in [forward.commit(), backward.commit()] {
+                let (last, accounts) = changes.split_last().unwrap();
+                assert!(matches!(last, (Key::MinterSet, StateOperation::Delete)));
+                let accounts = accounts.iter()
+                    .map(|change| match change {
+                        (Key::Account(public), StateOperation::Update(Value::Account(account))) => {
+                            (public.as_ref().to_vec(), account.bread)
+                        }
+                        _ => panic!("unexpected change"),
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(accounts, expected);
+            }
+        });
+    }
+
+    #[test]
+    fn test_noop() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0);
+            let mut state = init_state(context, config(alice.public_key()), &[(alice.public_key(), 1_000)]).await;
+
+            // Only consume the nonce
+            let tx = Transaction::sign(&alice, CHAIN_ID, 0, BASE_GAS, 0, Instruction::Noop);
+            let result = execute_state_transition(&mut state, vec![tx], 1, hash(1)).await.unwrap();
+            assert!(result.invalid_txs.is_empty());
+            assert!(result.events.is_empty());
+            assert_eq!(state.account(&alice.public_key()).await.unwrap(), Account { nonce: 1, bread: 1_000 });
+
+            // Pay the fee
+            let tx = Transaction::sign(&alice, CHAIN_ID, 1, BASE_GAS, 1, Instruction::Noop);
+            execute_state_transition(&mut state, vec![tx], 2, hash(2)).await.unwrap();
+            assert_eq!(state.account(&alice.public_key()).await.unwrap(), Account { nonce: 2, bread: 1_000 - BASE_GAS });
+        });
+    }
 }
\ No newline at end of file
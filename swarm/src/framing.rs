@@ -0,0 +1,115 @@
+use commonware_codec::{Decode, Encode, Error as CodecError};
+
+use thiserror::Error;
+
+use crate::types::Block;
+
+/// Magic bytes at the start of every block frame.
+pub const BLOCK_FRAME_MAGIC: [u8; 2] = [0xfc, 0xb1];
+
+/// Size of the frame header (magic followed by the big-endian body length).
+pub const BLOCK_FRAME_HEADER_SIZE: usize = BLOCK_FRAME_MAGIC.len() + 4;
+
+#[derive(Error, Debug)]
+pub enum FramingError {
+    #[error("invalid frame magic")]
+    InvalidMagic([u8; 2]),
+    #[error("truncated frame")]
+    Truncated(usize),
+    #[error("failed to decode block: {0}")]
+    Codec(#[from] CodecError),
+}
+
+/// A block framed for transport over a raw byte stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FramedBlock(pub Block);
+
+impl FramedBlock {
+    pub fn encode_framed(&self) -> Vec<u8> {
+        let body = self.0.encode();
+        let mut frame = Vec::with_capacity(BLOCK_FRAME_HEADER_SIZE + body.len());
+        frame.extend_from_slice(&BLOCK_FRAME_MAGIC);
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&body);
+        frame
+    }
+
+    /// Decode a single frame from the start of `buf`, returning the block and the number
+    /// of bytes consumed.
+    pub fn decode_framed(buf: &[u8]) -> Result<(Self, usize), FramingError> {
+        // Check header
+        if buf.len() < BLOCK_FRAME_HEADER_SIZE {
+            return Err(FramingError::Truncated(buf.len()));
+        }
+        let magic = [buf[0], buf[1]];
+        if magic != BLOCK_FRAME_MAGIC {
+            return Err(FramingError::InvalidMagic(magic));
+        }
+
+        // Check body length
+        let length = u32::from_be_bytes([buf[2], buf[3], buf[4], buf[5]]) as usize;
+        let end = BLOCK_FRAME_HEADER_SIZE + length;
+        if buf.len() < end {
+            return Err(FramingError::Truncated(buf.len()));
+        }
+
+        // Decode body
+        let block = Block::decode_cfg(&buf[BLOCK_FRAME_HEADER_SIZE..end], &())?;
+        Ok((Self(block), end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commonware_cryptography::{ed25519::PrivateKey, PrivateKeyExt};
+
+    use crate::types::{Instruction, Transaction, BASE_GAS};
+
+    fn framed_block() -> FramedBlock {
+        let signer = PrivateKey::from_seed(0);
+        let tx = Transaction::sign(&signer, 1, 0, BASE_GAS, 0, Instruction::Noop);
+        FramedBlock(Block::new([0; 32].into(), 1, vec![tx]))
+    }
+
+    #[test]
+    fn test_framing() {
+        let block = framed_block();
+        let mut buf = block.encode_framed();
+        let size = buf.len();
+
+        // Trailing bytes are left to the next frame
+        buf.extend_from_slice(&[0xff; 3]);
+        let (decoded, consumed) = FramedBlock::decode_framed(&buf).unwrap();
+        assert_eq!(decoded, block);
+        assert_eq!(consumed, size);
+    }
+
+    #[test]
+    fn test_framing_invalid_magic() {
+        let mut buf = framed_block().encode_framed();
+        buf[0] = 0;
+        assert!(matches!(
+            FramedBlock::decode_framed(&buf),
+            Err(FramingError::InvalidMagic([0, 0xb1]))
+        ));
+    }
+
+    #[test]
+    fn test_framing_truncated() {
+        let buf = framed_block().encode_framed();
+
+        // Truncated header
+        assert!(matches!(
+            FramedBlock::decode_framed(&buf[..3]),
+            Err(FramingError::Truncated(3))
+        ));
+
+        // Truncated body
+        let size = buf.len() - 1;
+        assert!(matches!(
+            FramedBlock::decode_framed(&buf[..size]),
+            Err(FramingError::Truncated(len)) if len == size
+        ));
+    }
+}
\ No newline at end of file
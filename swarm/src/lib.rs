@@ -1,2 +1,11 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod types;
-pub mod execution;
\ No newline at end of file
+#[cfg(feature = "std")]
+pub mod execution;
+#[cfg(feature = "std")]
+pub mod framing;
+#[cfg(feature = "rpc")]
+pub mod rpc;
\ No newline at end of file
@@ -0,0 +1,296 @@
+use std::sync::Arc;
+
+use commonware_codec::DecodeExt;
+use commonware_cryptography::{ed25519::PublicKey, sha256::Sha256};
+use commonware_runtime::{Clock, Error as RuntimeError, Listener, Metrics, Sink, Spawner, Storage, Stream};
+use commonware_storage::{mmr::hasher::Standard, translator::Translator};
+use commonware_utils::from_hex;
+use futures::lock::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    execution::{import_block, ImportError, State, StateTransitionResult},
+    types::Block,
+};
+
+/// Maximum size of a request head (request line and headers).
+const MAX_HEAD_SIZE: usize = 8 * 1024;
+
+/// Maximum size of a request body.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// A state shared between the node and the query server.
+///
+/// Queries hold the lock while reading, and the node holds it while executing blocks
+/// (see [import_shared_block]), so a query never observes a partially applied block.
+pub type SharedState<E, T> = Arc<Mutex<State<E, T>>>;
+
+/// A query request, e.g. `{"id": 1, "method": "get_balance", "params": {"public_key": "<hex>"}}`.
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    pub id: u64,
+    #[serde(flatten)]
+    pub query: Query,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum Query {
+    GetAccount { public_key: String },
+    GetBalance { public_key: String },
+    GetRoot,
+}
+
+/// A query response, holding either a result or an error.
+#[derive(Debug, Serialize)]
+pub struct Response {
+    pub id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<QueryResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum QueryResult {
+    Account { nonce: u64, bread: u64 },
+    Balance { balance: u64 },
+    Root { root: String },
+}
+
+/// Import a block into the shared state, holding the lock for the whole execution.
+pub async fn import_shared_block<E, T>(
+    state: &SharedState<E, T>,
+    block: &Block,
+    max_block_bytes: Option<usize>,
+) -> Result<StateTransitionResult, ImportError>
+where
+    E: Spawner + Metrics + Clock + Storage,
+    T: Translator,
+{
+    let mut state = state.lock().await;
+    import_block(&mut state, block, max_block_bytes).await
+}
+
+/// Serve state queries (one HTTP `POST` per connection) on the listener until it fails.
+pub async fn serve<E, T, L>(context: E, mut listener: L, state: SharedState<E, T>) -> Result<(), RuntimeError>
+where
+    E: Spawner + Metrics + Clock + Storage,
+    T: Translator + Send + Sync + 'static,
+    L: Listener,
+{
+    loop {
+        let (_, sink, stream) = listener.accept().await?;
+        let state = state.clone();
+        context.with_label("connection").spawn(move |_| async move {
+            // A failed connection only affects its client
+            let _ = handle_connection(sink, stream, state).await;
+        });
+    }
+}
+
+async fn handle_connection<E, T>(
+    mut sink: impl Sink,
+    mut stream: impl Stream,
+    state: SharedState<E, T>,
+) -> Result<(), RuntimeError>
+where
+    E: Spawner + Metrics + Clock + Storage,
+    T: Translator,
+{
+    let (status, body) = match read_request(&mut stream).await? {
+        Some(body) => match serde_json::from_slice::<Request>(&body) {
+            Ok(request) => ("200 OK", serde_json::to_vec(&handle(&state, request).await)),
+            Err(_) => ("400 Bad Request", Ok(Vec::new())),
+        },
+        None => ("400 Bad Request", Ok(Vec::new())),
+    };
+    let body = body.unwrap_or_default();
+
+    let mut response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    ).into_bytes();
+    response.extend_from_slice(&body);
+    sink.send(response).await
+}
+
+/// Read the body of an HTTP `POST` request, returning `None` if the request is malformed.
+async fn read_request(stream: &mut impl Stream) -> Result<Option<Vec<u8>>, RuntimeError> {
+    // Read the head (the stream only reads exact lengths, and heads are small)
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() == MAX_HEAD_SIZE {
+            return Ok(None);
+        }
+        let byte = stream.recv(vec![0u8; 1]).await?;
+        head.extend_from_slice(byte.as_ref());
+    }
+    let Ok(head) = std::str::from_utf8(&head) else {
+        return Ok(None);
+    };
+
+    // Check request line
+    let mut lines = head.split("\r\n");
+    if !lines.next().is_some_and(|line| line.starts_with("POST ")) {
+        return Ok(None);
+    }
+
+    // Find body length
+    let length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok());
+    let Some(length) = length.filter(|length| *length <= MAX_BODY_SIZE) else {
+        return Ok(None);
+    };
+    if length == 0 {
+        return Ok(Some(Vec::new()));
+    }
+
+    // Read body
+    let body = stream.recv(vec![0u8; length]).await?;
+    Ok(Some(body.as_ref().to_vec()))
+}
+
+async fn handle<E, T>(state: &SharedState<E, T>, request: Request) -> Response
+where
+    E: Spawner + Metrics + Clock + Storage,
+    T: Translator,
+{
+    let state = state.lock().await;
+    let outcome = match request.query {
+        Query::GetAccount { public_key } => match parse_public_key(&public_key) {
+            Some(public) => state.account(&public).await
+                .map(|account| QueryResult::Account { nonce: account.nonce, bread: account.bread })
+                .map_err(|err| err.to_string()),
+            None => Err("invalid public key".to_string()),
+        },
+        Query::GetBalance { public_key } => match parse_public_key(&public_key) {
+            Some(public) => state.balance(&public).await
+                .map(|balance| QueryResult::Balance { balance })
+                .map_err(|err| err.to_string()),
+            None => Err("invalid public key".to_string()),
+        },
+        Query::GetRoot => {
+            let mut hasher = Standard::<Sha256>::new();
+            Ok(QueryResult::Root { root: state.root(&mut hasher).to_string() })
+        }
+    };
+
+    let (result, error) = match outcome {
+        Ok(result) => (Some(result), None),
+        Err(err) => (None, Some(err)),
+    };
+    Response { id: request.id, result, error }
+}
+
+fn parse_public_key(public_key: &str) -> Option<PublicKey> {
+    PublicKey::decode(from_hex(public_key)?.as_ref()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        net::SocketAddr,
+        num::{NonZeroU64, NonZeroUsize},
+    };
+
+    use commonware_cryptography::{ed25519::PrivateKey, PrivateKeyExt, Signer};
+    use commonware_runtime::{buffer::PoolRef, deterministic, Network, Runner};
+    use commonware_storage::{adb::any::variable::{Any, Config as AdbConfig}, translator::EightCap};
+    use commonware_utils::hex;
+
+    use crate::{
+        execution::{Config, StateOperation},
+        types::{Account, CommitMetadata, Key, Value},
+    };
+
+    /// Create a state holding a single funded account (committed at height 0).
+    async fn init_state(context: deterministic::Context, public: PublicKey, bread: u64) -> SharedState<deterministic::Context, EightCap> {
+        let adb = Any::init(context.with_label("adb"), AdbConfig {
+            mmr_journal_partition: "state_mmr_journal".into(),
+            mmr_metadata_partition: "state_mmr_metadata".into(),
+            mmr_items_per_blob: NonZeroU64::new(7).unwrap(),
+            mmr_write_buffer: NonZeroUsize::new(1024).unwrap(),
+            log_journal_partition: "state_log_journal".into(),
+            log_items_per_section: NonZeroU64::new(7).unwrap(),
+            log_write_buffer: NonZeroUsize::new(1024).unwrap(),
+            log_compression: None,
+            log_codec_config: (),
+            locations_journal_partition: "state_locations_journal".into(),
+            locations_items_per_blob: NonZeroU64::new(7).unwrap(),
+            translator: EightCap,
+            thread_pool: None,
+            buffer_pool: PoolRef::new(NonZeroUsize::new(1024).unwrap(), NonZeroUsize::new(16).unwrap()),
+        })
+        .await
+        .expect("failed to initialize adb");
+        let mut state = State::new(context.with_label("state"), adb, Config {
+            chain_id: 1,
+            genesis_block_hash: [0; 32].into(),
+            governance_key: public.clone(),
+            min_transfer_amount: 0,
+            max_height: None,
+            event_sender: None,
+        });
+        let changes = vec![(
+            Key::Account(public),
+            StateOperation::Update(Value::Account(Account { nonce: 0, bread })),
+        )];
+        let metadata = CommitMetadata { height: 0, start: 0, head: [0; 32].into() };
+        state.apply(changes, metadata).await.unwrap();
+        Arc::new(Mutex::new(state))
+    }
+
+    /// Read a whole HTTP response, returning its head and its body.
+    async fn read_response(stream: &mut impl Stream) -> (String, Vec<u8>) {
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            let byte = stream.recv(vec![0u8; 1]).await.unwrap();
+            head.extend_from_slice(byte.as_ref());
+        }
+        let head = String::from_utf8(head).unwrap();
+        let length = head
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .and_then(|length| length.parse::<usize>().ok())
+            .unwrap();
+        let body = stream.recv(vec![0u8; length]).await.unwrap();
+        (head, body.as_ref().to_vec())
+    }
+
+    #[test]
+    fn test_balance_query() {
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let alice = PrivateKey::from_seed(0).public_key();
+            let state = init_state(context.clone(), alice.clone(), 100).await;
+
+            // Start server
+            let address = SocketAddr::from(([127, 0, 0, 1], 8080));
+            let listener = context.bind(address).await.unwrap();
+            let _server = context.with_label("rpc").spawn(move |context| serve(context, listener, state));
+
+            // Query the balance over a loopback connection
+            let (mut sink, mut stream) = context.dial(address).await.unwrap();
+            let body = format!(
+                r#"{{"id": 7, "method": "get_balance", "params": {{"public_key": "{}"}}}}"#,
+                hex(alice.as_ref()),
+            );
+            let request = format!(
+                "POST / HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len(),
+            );
+            sink.send(request.into_bytes()).await.unwrap();
+
+            let (head, body) = read_response(&mut stream).await;
+            assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+            let response: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(response, serde_json::json!({"id": 7, "result": {"balance": 100}}));
+        });
+    }
+}
\ No newline at end of file
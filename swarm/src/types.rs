@@ -1,24 +1,48 @@
+use alloc::{vec, vec::Vec};
+use core::{cmp::Ordering, fmt};
+
 use commonware_cryptography::{
-    Digestible, Hasher,
+    Digestible, Hasher, Signer, Verifier,
     Committable,
-    ed25519::{PublicKey, Signature},
+    ed25519::{PrivateKey, PublicKey, Signature},
     sha256::{Digest, Sha256},
 };
 use commonware_codec::{
     Write, Read, EncodeSize, Error as CodecError,
-    Encode, ReadExt, RangeCfg,
+    Encode, DecodeExt, ReadExt, RangeCfg,
     varint::UInt,
 };
 
 use bytes::{Buf, BufMut};
 
+#[cfg(feature = "std")]
 use fcn_common::mempool::MempoolTransaction;
 
 pub const MAX_BLOCK_TRANSACTIONS: usize = 10;
 
+/// The maximum number of authorized minters.
+pub const MAX_MINTERS: usize = 64;
+
+/// The maximum size of a transfer memo (in bytes).
+pub const MAX_MEMO_SIZE: usize = 64;
+
+/// Base gas cost of any instruction.
+pub const BASE_GAS: u64 = 100;
+
+/// Gas cost of each byte of variable-size instruction data.
+pub const GAS_PER_BYTE: u64 = 1;
+
+/// Get the config to decode a list of at most `max` transactions.
+pub fn transaction_list_cfg(max: usize) -> (RangeCfg, ()) {
+    (RangeCfg::from(0..=max), ())
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Transaction {
+    pub chain_id: u64,
     pub nonce: u64,
+    pub gas_limit: u64,
+    pub gas_price: u64,
     pub instruction: Instruction,
 
     pub public_key: PublicKey,
@@ -27,7 +51,10 @@ pub struct Transaction {
 
 impl Write for Transaction {
     fn write(&self, buf: &mut impl BufMut) {
+        self.chain_id.write(buf);
         self.nonce.write(buf);
+        self.gas_limit.write(buf);
+        self.gas_price.write(buf);
         self.instruction.write(buf);
         self.public_key.write(buf);
         self.signature.write(buf);
@@ -36,7 +63,10 @@ impl Write for Transaction {
 
 impl EncodeSize for Transaction {
     fn encode_size(&self) -> usize {
-        self.nonce.encode_size()
+        self.chain_id.encode_size()
+            + self.nonce.encode_size()
+            + self.gas_limit.encode_size()
+            + self.gas_price.encode_size()
             + self.instruction.encode_size()
             + self.public_key.encode_size()
             + self.signature.encode_size()
@@ -46,12 +76,18 @@ impl EncodeSize for Transaction {
 impl Read for Transaction {
     type Cfg = ();
     fn read_cfg(buf: &mut impl Buf, _: &()) -> Result<Self, CodecError> {
+        let chain_id = u64::read(buf)?;
         let nonce = u64::read(buf)?;
+        let gas_limit = u64::read(buf)?;
+        let gas_price = u64::read(buf)?;
         let instruction = Instruction::read(buf)?;
         let public_key = PublicKey::read(buf)?;
         let signature = Signature::read(buf)?;
         Ok(Self{
+            chain_id,
             nonce,
+            gas_limit,
+            gas_price,
             instruction,
             public_key,
             signature,
@@ -59,6 +95,15 @@ impl Read for Transaction {
     }
 }
 
+impl TryFrom<&[u8]> for Transaction {
+    type Error = CodecError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::decode(bytes)
+    }
+}
+
+#[cfg(feature = "std")]
 impl MempoolTransaction for Transaction {
     fn public_key(&self) -> PublicKey {
         self.public_key.clone()
@@ -67,25 +112,163 @@ impl MempoolTransaction for Transaction {
     fn nonce(&self) -> u64 {
         self.nonce
     }
+
+    fn priority(&self) -> u64 {
+        self.gas_price
+    }
 }
 
-impl Digestible for Transaction {
-    type Digest = Digest;
+/// A decoding error annotated with where it happened.
+#[derive(Debug)]
+pub struct DecodeDiagnostic {
+    /// Offset (in bytes) at which the failing field starts.
+    pub offset: usize,
+    pub field: &'static str,
+    pub error: CodecError,
+}
 
-    fn digest(&self) -> Digest {
-        let mut hasher = Sha256::new();
-        hasher.update(self.nonce.to_be_bytes().as_ref());
-        hasher.update(self.instruction.encode().as_ref());
-        hasher.update(self.public_key.as_ref());
+/// Read a single field, annotating errors with the field name and offset.
+fn read_field<T>(
+    buf: &mut &[u8],
+    len: usize,
+    field: &'static str,
+    read: impl FnOnce(&mut &[u8]) -> Result<T, CodecError>,
+) -> Result<T, DecodeDiagnostic> {
+    let offset = len - buf.len();
+    read(buf).map_err(|error| DecodeDiagnostic { offset, field, error })
+}
+
+impl Transaction {
+    /// Decode a transaction, reporting the offset and field of any error.
+    pub fn decode_with_diagnostics(mut buf: &[u8]) -> Result<Self, DecodeDiagnostic> {
+        let len = buf.len();
+        let buf = &mut buf;
+        let chain_id = read_field(buf, len, "chain_id", u64::read)?;
+        let nonce = read_field(buf, len, "nonce", u64::read)?;
+        let gas_limit = read_field(buf, len, "gas_limit", u64::read)?;
+        let gas_price = read_field(buf, len, "gas_price", u64::read)?;
+        let instruction = read_field(buf, len, "instruction", Instruction::read)?;
+        let public_key = read_field(buf, len, "public_key", PublicKey::read)?;
+        let signature = read_field(buf, len, "signature", Signature::read)?;
+        if !buf.is_empty() {
+            return Err(DecodeDiagnostic {
+                offset: len - buf.len(),
+                field: "end",
+                error: CodecError::ExtraData(buf.len()),
+            });
+        }
+        Ok(Self{
+            chain_id,
+            nonce,
+            gas_limit,
+            gas_price,
+            instruction,
+            public_key,
+            signature,
+        })
+    }
+
+    /// Check the signature of the transaction against its public key.
+    pub fn verify_signature(&self) -> bool {
+        self.public_key.verify(None, &self.digest(), &self.signature)
+    }
+
+    /// Maximum fee paid by the transaction (charged up front), if it doesn't overflow.
+    pub fn max_fee(&self) -> Option<u64> {
+        self.gas_limit.checked_mul(self.gas_price)
+    }
+
+    /// Create a transaction signed by the given key.
+    pub fn sign(
+        signer: &PrivateKey,
+        chain_id: u64,
+        nonce: u64,
+        gas_limit: u64,
+        gas_price: u64,
+        instruction: Instruction,
+    ) -> Self {
+        let public_key = signer.public_key();
+        let digest = Self::compute_digest::<Sha256>(
+            chain_id,
+            nonce,
+            gas_limit,
+            gas_price,
+            &instruction,
+            &public_key,
+        );
+        let signature = signer.sign(None, &digest);
+        Self {
+            chain_id,
+            nonce,
+            gas_limit,
+            gas_price,
+            instruction,
+            public_key,
+            signature,
+        }
+    }
+
+    /// Compute the transaction digest with the given hasher.
+    pub fn digest_with<H: Hasher>(&self) -> H::Digest {
+        Self::compute_digest::<H>(
+            self.chain_id,
+            self.nonce,
+            self.gas_limit,
+            self.gas_price,
+            &self.instruction,
+            &self.public_key,
+        )
+    }
+
+    fn compute_digest<H: Hasher>(
+        chain_id: u64,
+        nonce: u64,
+        gas_limit: u64,
+        gas_price: u64,
+        instruction: &Instruction,
+        public_key: &PublicKey,
+    ) -> H::Digest {
+        let mut hasher = H::new();
+        hasher.update(chain_id.to_be_bytes().as_ref());
+        hasher.update(nonce.to_be_bytes().as_ref());
+        hasher.update(gas_limit.to_be_bytes().as_ref());
+        hasher.update(gas_price.to_be_bytes().as_ref());
+        hasher.update(instruction.encode().as_ref());
+        hasher.update(public_key.as_ref());
         // We don't include the signature as part of the digest (any valid
         // signature will be valid for the transaction)
         hasher.finalize()
     }
 }
 
+impl Digestible for Transaction {
+    type Digest = Digest;
+
+    fn digest(&self) -> Digest {
+        self.digest_with::<Sha256>()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Instruction {
     TransferBread(TransferBread),
+    MintBread(MintBread),
+    UpdateMinters(UpdateMinters),
+    TransferBreadMemo(TransferBreadMemo),
+    /// Only consume the nonce (and the fee) of the sender, e.g. for liveness or to replace a
+    /// stuck transaction.
+    Noop,
+}
+
+impl Instruction {
+    /// Gas consumed by executing the instruction.
+    pub fn gas_cost(&self) -> u64 {
+        BASE_GAS + match self {
+            Instruction::TransferBread(_) | Instruction::MintBread(_) | Instruction::Noop => 0,
+            Instruction::UpdateMinters(i) => i.minters.encode_size() as u64 * GAS_PER_BYTE,
+            Instruction::TransferBreadMemo(i) => i.memo.len() as u64 * GAS_PER_BYTE,
+        }
+    }
 }
 
 impl Write for Instruction {
@@ -95,6 +278,21 @@ impl Write for Instruction {
                 0u8.write(buf);
                 i.write(buf);
             }
+            Instruction::MintBread(i) => {
+                1u8.write(buf);
+                i.write(buf);
+            }
+            Instruction::UpdateMinters(i) => {
+                2u8.write(buf);
+                i.write(buf);
+            }
+            Instruction::TransferBreadMemo(i) => {
+                3u8.write(buf);
+                i.write(buf);
+            }
+            Instruction::Noop => {
+                4u8.write(buf);
+            }
         }
     }
 }
@@ -102,7 +300,11 @@ impl Write for Instruction {
 impl EncodeSize for Instruction {
     fn encode_size(&self) -> usize {
         1 + match self {
-            Instruction::TransferBread(i) => i.encode_size()
+            Instruction::TransferBread(i) => i.encode_size(),
+            Instruction::MintBread(i) => i.encode_size(),
+            Instruction::UpdateMinters(i) => i.encode_size(),
+            Instruction::TransferBreadMemo(i) => i.encode_size(),
+            Instruction::Noop => 0,
         }
     }
 }
@@ -113,6 +315,10 @@ impl Read for Instruction {
         let tag = u8::read(buf)?;
         match tag {
             0 => Ok(Instruction::TransferBread(TransferBread::read(buf)?)),
+            1 => Ok(Instruction::MintBread(MintBread::read(buf)?)),
+            2 => Ok(Instruction::UpdateMinters(UpdateMinters::read(buf)?)),
+            3 => Ok(Instruction::TransferBreadMemo(TransferBreadMemo::read(buf)?)),
+            4 => Ok(Instruction::Noop),
             d => Err(CodecError::InvalidEnum(d)),
         }
     }
@@ -150,6 +356,118 @@ impl Read for TransferBread {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransferBreadMemo {
+    pub amount: u64,
+    pub to: PublicKey,
+    pub memo: Vec<u8>,
+}
+
+impl TransferBreadMemo {
+    /// Get the transfer without its memo.
+    pub fn transfer(&self) -> TransferBread {
+        TransferBread {
+            amount: self.amount,
+            to: self.to.clone(),
+        }
+    }
+}
+
+impl Write for TransferBreadMemo {
+    fn write(&self, buf: &mut impl BufMut) {
+        self.amount.write(buf);
+        self.to.write(buf);
+        self.memo.write(buf);
+    }
+}
+
+impl EncodeSize for TransferBreadMemo {
+    fn encode_size(&self) -> usize {
+        self.amount.encode_size()
+            + self.to.encode_size()
+            + self.memo.encode_size()
+    }
+}
+
+impl Read for TransferBreadMemo {
+    type Cfg = ();
+    fn read_cfg(buf: &mut impl Buf, _: &()) -> Result<Self, CodecError> {
+        let amount = u64::read(buf)?;
+        let to = PublicKey::read(buf)?;
+        let memo = Vec::<u8>::read_cfg(
+            buf,
+            &(RangeCfg::from(0..=MAX_MEMO_SIZE), ()),
+        )?;
+        Ok(Self{
+            amount,
+            to,
+            memo,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MintBread {
+    pub amount: u64,
+    pub to: PublicKey,
+}
+
+impl Write for MintBread {
+    fn write(&self, buf: &mut impl BufMut) {
+        self.amount.write(buf);
+        self.to.write(buf);
+    }
+}
+
+impl EncodeSize for MintBread {
+    fn encode_size(&self) -> usize {
+        self.amount.encode_size()
+            + self.to.encode_size()
+    }
+}
+
+impl Read for MintBread {
+    type Cfg = ();
+    fn read_cfg(buf: &mut impl Buf, _: &()) -> Result<Self, CodecError> {
+        let amount = u64::read(buf)?;
+        let to = PublicKey::read(buf)?;
+        Ok(Self{
+            amount,
+            to,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UpdateMinters {
+    pub minters: Vec<PublicKey>,
+}
+
+impl Write for UpdateMinters {
+    fn write(&self, buf: &mut impl BufMut) {
+        self.minters.write(buf);
+    }
+}
+
+impl EncodeSize for UpdateMinters {
+    fn encode_size(&self) -> usize {
+        self.minters.encode_size()
+    }
+}
+
+impl Read for UpdateMinters {
+    type Cfg = ();
+    fn read_cfg(buf: &mut impl Buf, _: &()) -> Result<Self, CodecError> {
+        let minters = Vec::<PublicKey>::read_cfg(
+            buf,
+            &(RangeCfg::from(0..=MAX_MINTERS), ()),
+        )?;
+        Ok(Self{
+            minters,
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Block {
     pub parent: Digest,
@@ -158,33 +476,141 @@ pub struct Block {
     digest: Digest,
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum BlockError {
+    TooManyTransactions(usize),
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockError::TooManyTransactions(_) => f.write_str("too many block transactions"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BlockError {}
+
 impl Block {
+    /// Create a block from trusted transactions (panics if there are too many).
     pub fn new(parent: Digest, height: u64, transactions: Vec<Transaction>) -> Self {
-        assert!(transactions.len() <= MAX_BLOCK_TRANSACTIONS);
-        let digest = Self::compute_digest(&parent, height, &transactions);
-        Self {
+        Self::try_new(parent, height, transactions).expect("too many block transactions")
+    }
+
+    pub fn try_new(parent: Digest, height: u64, transactions: Vec<Transaction>) -> Result<Self, BlockError> {
+        if transactions.len() > MAX_BLOCK_TRANSACTIONS {
+            return Err(BlockError::TooManyTransactions(transactions.len()));
+        }
+        let digest = Self::compute_digest::<Sha256>(&parent, height, &transactions);
+        Ok(Self {
             parent,
             height,
             transactions,
             digest,
-        }
+        })
     }
 
-    fn compute_digest(
+    /// Get the size of the encoded block (in bytes).
+    pub fn size_bytes(&self) -> usize {
+        self.encode_size()
+    }
+
+    /// Check that the stored digest matches the block contents.
+    pub fn verify(&self) -> bool {
+        self.digest == Self::compute_digest::<Sha256>(&self.parent, self.height, &self.transactions)
+    }
+
+    /// Return the indices of transactions with an invalid signature.
+    pub fn verify_transactions(&self) -> Vec<usize> {
+        self.transactions.iter()
+            .enumerate()
+            .filter(|(_, tx)| !tx.verify_signature())
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Compute the block digest with the given hasher.
+    pub fn digest_with<H: Hasher>(&self) -> H::Digest {
+        Self::compute_digest::<H>(&self.parent, self.height, &self.transactions)
+    }
+
+    fn compute_digest<H: Hasher>(
         parent: &Digest,
         height: u64,
         transactions: &[Transaction],
-    ) -> Digest {
-        let mut hasher = Sha256::new();
+    ) -> H::Digest {
+        let mut hasher = H::new();
         hasher.update(parent);
         hasher.update(&height.to_be_bytes());
         for transaction in transactions {
-            hasher.update(&transaction.digest());
+            hasher.update(&transaction.digest_with::<H>());
         }
         hasher.finalize()
     }
 }
 
+/// Assembles a [Block] from candidate transactions (e.g. served by the mempool), up to
+/// [MAX_BLOCK_TRANSACTIONS].
+pub struct BlockBuilder {
+    parent: Digest,
+    height: u64,
+    /// Whether transactions of the same sender are grouped together (by nonce) in the block.
+    coalesce_senders: bool,
+    transactions: Vec<Transaction>,
+}
+
+impl BlockBuilder {
+    pub fn new(parent: Digest, height: u64, coalesce_senders: bool) -> Self {
+        Self {
+            parent,
+            height,
+            coalesce_senders,
+            transactions: Vec::new(),
+        }
+    }
+
+    /// Check if the block can't include more transactions.
+    pub fn is_full(&self) -> bool {
+        self.transactions.len() >= MAX_BLOCK_TRANSACTIONS
+    }
+
+    /// Add a transaction to the block, returning it back if the block is full.
+    pub fn push(&mut self, tx: Transaction) -> Result<(), Transaction> {
+        if self.is_full() {
+            return Err(tx);
+        }
+        self.transactions.push(tx);
+        Ok(())
+    }
+
+    pub fn build(self) -> Block {
+        let transactions = if self.coalesce_senders {
+            Self::coalesce(self.transactions)
+        } else {
+            self.transactions
+        };
+        Block::new(self.parent, self.height, transactions)
+    }
+
+    /// Group transactions by sender (in order of first appearance), ordering each group by nonce.
+    fn coalesce(transactions: Vec<Transaction>) -> Vec<Transaction> {
+        let mut groups = Vec::<(PublicKey, Vec<Transaction>)>::new();
+        for tx in transactions {
+            match groups.iter_mut().find(|(sender, _)| *sender == tx.public_key) {
+                Some((_, group)) => group.push(tx),
+                None => groups.push((tx.public_key.clone(), vec![tx])),
+            }
+        }
+        groups.into_iter()
+            .flat_map(|(_, mut group)| {
+                group.sort_by_key(|tx| tx.nonce);
+                group
+            })
+            .collect()
+    }
+}
+
 impl Write for Block {
     fn write(&self, writer: &mut impl BufMut) {
         self.parent.write(writer);
@@ -201,11 +627,11 @@ impl Read for Block {
         let height = UInt::read(reader)?.into();
         let transactions = Vec::<Transaction>::read_cfg(
             reader,
-            &(RangeCfg::from(0..=MAX_BLOCK_TRANSACTIONS), ()),
+            &transaction_list_cfg(MAX_BLOCK_TRANSACTIONS),
         )?;
 
         // Pre-compute the digest
-        let digest = Self::compute_digest(&parent, height, &transactions);
+        let digest = Self::compute_digest::<Sha256>(&parent, height, &transactions);
         Ok(Self {
             parent,
             height,
@@ -215,6 +641,14 @@ impl Read for Block {
     }
 }
 
+impl TryFrom<&[u8]> for Block {
+    type Error = CodecError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::decode(bytes)
+    }
+}
+
 impl EncodeSize for Block {
     fn encode_size(&self) -> usize {
         self.parent.encode_size()
@@ -245,6 +679,16 @@ pub struct Account {
     pub bread: u64,
 }
 
+impl Account {
+    pub fn can_afford(&self, amount: u64) -> bool {
+        self.bread >= amount
+    }
+
+    pub fn expects_nonce(&self, nonce: u64) -> bool {
+        self.nonce == nonce
+    }
+}
+
 impl Write for Account {
     fn write(&self, buf: &mut impl BufMut) {
         self.nonce.write(buf);
@@ -275,12 +719,38 @@ impl Read for Account {
 pub struct CommitMetadata {
     pub height: u64,
     pub start: u64,
+    /// Digest of the block committed at this height.
+    pub head: Digest,
+}
+
+impl CommitMetadata {
+    /// Check if the commit was made at a later point than the other one.
+    pub fn is_ahead_of(&self, other: &CommitMetadata) -> bool {
+        (self.height, self.start) > (other.height, other.start)
+    }
+}
+
+impl Ord for CommitMetadata {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Order by progress (the head only keeps the order consistent with Eq)
+        self.height
+            .cmp(&other.height)
+            .then_with(|| self.start.cmp(&other.start))
+            .then_with(|| self.head.cmp(&other.head))
+    }
+}
+
+impl PartialOrd for CommitMetadata {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl Write for CommitMetadata {
     fn write(&self, buf: &mut impl BufMut) {
         self.height.write(buf);
         self.start.write(buf);
+        self.head.write(buf);
     }
 }
 
@@ -288,6 +758,7 @@ impl EncodeSize for CommitMetadata {
     fn encode_size(&self) -> usize {
         self.height.encode_size()
             + self.start.encode_size()
+            + self.head.encode_size()
     }
 }
 
@@ -296,16 +767,39 @@ impl Read for CommitMetadata {
     fn read_cfg(buf: &mut impl Buf, _: &()) -> Result<Self, CodecError> {
         let height = u64::read(buf)?;
         let start = u64::read(buf)?;
+        let head = Digest::read(buf)?;
         Ok(Self{
             height,
             start,
+            head,
         })
     }
 }
 
+/// The number of public key bytes shown when debugging a key.
+const KEY_DEBUG_PREFIX: usize = 4;
+
+/// A state key (the derived ordering sets the order in which changes are committed, so
+/// variants must not be reordered).
 #[derive(Hash, Eq, PartialEq, Ord, PartialOrd, Clone)]
 pub enum Key {
     Account(PublicKey),
+    MinterSet,
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Account(k) => {
+                f.write_str("Account(")?;
+                for byte in &k.as_ref()[..KEY_DEBUG_PREFIX] {
+                    write!(f, "{byte:02x}")?;
+                }
+                f.write_str("..)")
+            }
+            Key::MinterSet => f.write_str("MinterSet"),
+        }
+    }
 }
 
 impl Write for Key {
@@ -315,6 +809,9 @@ impl Write for Key {
                 0u8.write(buf);
                 k.write(buf);
             }
+            Key::MinterSet => {
+                1u8.write(buf);
+            }
         }
     }
 }
@@ -322,7 +819,8 @@ impl Write for Key {
 impl EncodeSize for Key {
     fn encode_size(&self) -> usize {
         1 + match self {
-            Key::Account(k) => k.encode_size()
+            Key::Account(k) => k.encode_size(),
+            Key::MinterSet => 0,
         }
     }
 }
@@ -333,6 +831,7 @@ impl Read for Key {
         let tag = u8::read(buf)?;
         match tag {
             0 => Ok(Key::Account(PublicKey::read(buf)?)),
+            1 => Ok(Key::MinterSet),
             d => Err(CodecError::InvalidEnum(d)),
         }
     }
@@ -342,6 +841,7 @@ impl Read for Key {
 pub enum Value {
     Account(Account),
     CommitMetadata(CommitMetadata),
+    MinterSet(Vec<PublicKey>),
 }
 
 impl Write for Value {
@@ -353,7 +853,11 @@ impl Write for Value {
             },
             Value::CommitMetadata(v) => {
                 1u8.write(buf);
-                v.height.write(buf);
+                v.write(buf);
+            },
+            Value::MinterSet(v) => {
+                2u8.write(buf);
+                v.write(buf);
             },
         }
     }
@@ -364,6 +868,7 @@ impl EncodeSize for Value {
         1 + match self {
             Value::Account(v) => v.encode_size(),
             Value::CommitMetadata(v) => v.encode_size(),
+            Value::MinterSet(v) => v.encode_size(),
         }
     }
 }
@@ -375,7 +880,304 @@ impl Read for Value {
         match tag {
             0 => Ok(Value::Account(Account::read(buf)?)),
             1 => Ok(Value::CommitMetadata(CommitMetadata::read(buf)?)),
+            2 => Ok(Value::MinterSet(Vec::<PublicKey>::read_cfg(
+                buf,
+                &(RangeCfg::from(0..=MAX_MINTERS), ()),
+            )?)),
             d => Err(CodecError::InvalidEnum(d)),
         }
     }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use commonware_codec::Decode;
+    use commonware_cryptography::PrivateKeyExt;
+    use commonware_runtime::{deterministic, Runner};
+    use fcn_common::mempool::{AddResult, Mempool, MempoolConfig};
+
+    /// Encoding of the ed25519 base point, a valid public key.
+    const BASE_POINT: [u8; 32] = [
+        0x58, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66,
+        0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66,
+    ];
+
+    fn noop(signer: &PrivateKey, nonce: u64) -> Transaction {
+        Transaction::sign(signer, 1, nonce, BASE_GAS, 0, Instruction::Noop)
+    }
+
+    /// A transaction with fixed contents (and an invalid signature).
+    fn fixed_transaction() -> Transaction {
+        Transaction {
+            chain_id: 1,
+            nonce: 2,
+            gas_limit: 100,
+            gas_price: 3,
+            instruction: Instruction::Noop,
+            public_key: PublicKey::decode(&BASE_POINT[..]).unwrap(),
+            signature: Signature::decode(&[0u8; 64][..]).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_default_digest() {
+        // Digests of fixed inputs must never change
+        let tx = fixed_transaction();
+        assert_eq!(tx.digest(), tx.digest_with::<Sha256>());
+        assert_eq!(
+            tx.digest().to_string(),
+            "3aaf2167b83d03dd6108f087fbf661d514c73a1705d6b4170dba9efe6b6bd0a8",
+        );
+
+        let block = Block::new([0; 32].into(), 1, vec![tx]);
+        assert_eq!(block.digest(), block.digest_with::<Sha256>());
+        assert_eq!(
+            block.digest().to_string(),
+            "9301ca7208824df3ed4e4d4bf77fa75dc51a49606dc0e1508f3106106ffc9a13",
+        );
+    }
+
+    #[test]
+    fn test_block_verify() {
+        let signer = PrivateKey::from_seed(0);
+        let block = Block::new([0; 32].into(), 1, vec![noop(&signer, 0), noop(&signer, 1)]);
+        assert!(block.verify());
+        assert!(block.verify_transactions().is_empty());
+
+        // Changing the contents invalidates the stored digest
+        let mut tampered = block.clone();
+        tampered.height = 2;
+        assert!(!tampered.verify());
+        let mut tampered = block;
+        tampered.transactions.pop();
+        assert!(!tampered.verify());
+
+        // Report transactions with a bad signature
+        let block = Block::new([0; 32].into(), 1, vec![noop(&signer, 0), fixed_transaction()]);
+        assert!(block.verify());
+        assert_eq!(block.verify_transactions(), vec![1]);
+    }
+
+    #[test]
+    fn test_update_minters_codec() {
+        let minters = (0..MAX_MINTERS as u64)
+            .map(|seed| PrivateKey::from_seed(seed).public_key())
+            .collect::<Vec<_>>();
+        let instruction = Instruction::UpdateMinters(UpdateMinters { minters: minters.clone() });
+        let encoded = instruction.encode();
+        assert_eq!(encoded.len(), instruction.encode_size());
+        assert_eq!(encoded[0], 2);
+        assert_eq!(Instruction::decode(encoded).unwrap(), instruction);
+
+        // Reject sets above the maximum
+        let mut minters = minters;
+        minters.push(PrivateKey::from_seed(MAX_MINTERS as u64).public_key());
+        let instruction = Instruction::UpdateMinters(UpdateMinters { minters });
+        assert!(Instruction::decode(instruction.encode()).is_err());
+    }
+
+    #[test]
+    fn test_account_checks() {
+        let account = Account { nonce: 3, bread: 10 };
+
+        // Balance boundaries
+        assert!(account.can_afford(0));
+        assert!(account.can_afford(10));
+        assert!(!account.can_afford(11));
+
+        // Nonce boundaries
+        assert!(account.expects_nonce(3));
+        assert!(!account.expects_nonce(2));
+        assert!(!account.expects_nonce(4));
+    }
+
+    #[test]
+    fn test_transfer_memo_codec() {
+        let signer = PrivateKey::from_seed(0);
+        let to = PrivateKey::from_seed(1).public_key();
+        let memo = |memo: Vec<u8>| Instruction::TransferBreadMemo(TransferBreadMemo { amount: 5, to: to.clone(), memo });
+
+        let instruction = memo(vec![7; MAX_MEMO_SIZE]);
+        let encoded = instruction.encode();
+        assert_eq!(encoded.len(), instruction.encode_size());
+        assert_eq!(encoded[0], 3);
+        assert_eq!(Instruction::decode(encoded).unwrap(), instruction);
+
+        // The memo is signed
+        let tx = Transaction::sign(&signer, 1, 0, BASE_GAS, 0, memo(vec![1]));
+        let other = Transaction::sign(&signer, 1, 0, BASE_GAS, 0, memo(vec![2]));
+        assert_ne!(tx.digest(), other.digest());
+
+        // Reject memos above the maximum
+        let instruction = memo(vec![7; MAX_MEMO_SIZE + 1]);
+        assert!(Instruction::decode(instruction.encode()).is_err());
+    }
+
+    #[test]
+    fn test_key_debug() {
+        let key = Key::Account(PublicKey::decode(&BASE_POINT[..]).unwrap());
+        assert_eq!(format!("{key:?}"), "Account(58666666..)");
+        assert_eq!(format!("{:?}", Key::MinterSet), "MinterSet");
+    }
+
+    #[test]
+    fn test_sign() {
+        let signer = PrivateKey::from_seed(0);
+        let to = PrivateKey::from_seed(1).public_key();
+        let instruction = Instruction::TransferBread(TransferBread { amount: 5, to });
+        let tx = Transaction::sign(&signer, 1, 0, BASE_GAS, 2, instruction);
+        assert_eq!(tx.public_key, signer.public_key());
+        assert!(tx.verify_signature());
+
+        // The signature covers the digest of the transaction
+        let mut tampered = tx.clone();
+        tampered.nonce = 1;
+        assert!(!tampered.verify_signature());
+
+        // The mempool accepts it
+        let executor = deterministic::Runner::default();
+        executor.start(|context| async move {
+            let mut mempool = Mempool::new(context, MempoolConfig::default());
+            assert_eq!(mempool.add(tx.clone()), AddResult::Added);
+            assert_eq!(mempool.next(0), Some(tx));
+        });
+    }
+
+    #[test]
+    fn test_block_try_new() {
+        let signer = PrivateKey::from_seed(0);
+        let txs = (0..=MAX_BLOCK_TRANSACTIONS as u64).map(|nonce| noop(&signer, nonce)).collect::<Vec<_>>();
+
+        // Reject blocks above the maximum
+        assert_eq!(
+            Block::try_new([0; 32].into(), 1, txs.clone()),
+            Err(BlockError::TooManyTransactions(MAX_BLOCK_TRANSACTIONS + 1)),
+        );
+
+        // Accept blocks at the maximum
+        let mut txs = txs;
+        txs.pop();
+        let block = Block::try_new([0; 32].into(), 1, txs.clone()).unwrap();
+        assert_eq!(block, Block::new([0; 32].into(), 1, txs));
+    }
+
+    #[test]
+    fn test_commit_metadata_codec() {
+        let metadata = CommitMetadata { height: 3, start: 42, head: [7; 32].into() };
+        let encoded = metadata.encode();
+        assert_eq!(encoded.len(), metadata.encode_size());
+        assert_eq!(CommitMetadata::decode(encoded).unwrap(), metadata);
+
+        // Every field is written as a value
+        let value = Value::CommitMetadata(metadata);
+        assert_eq!(Value::decode(value.encode()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_decode_diagnostics() {
+        let tx = noop(&PrivateKey::from_seed(0), 0);
+        let encoded = tx.encode();
+        assert_eq!(Transaction::decode_with_diagnostics(&encoded).unwrap(), tx);
+
+        // Report the field being read when bytes run out (four u64 and the instruction tag
+        // precede the public key)
+        let diagnostic = Transaction::decode_with_diagnostics(&encoded[..50]).unwrap_err();
+        assert_eq!((diagnostic.offset, diagnostic.field), (33, "public_key"));
+        let diagnostic = Transaction::decode_with_diagnostics(&encoded[..100]).unwrap_err();
+        assert_eq!((diagnostic.offset, diagnostic.field), (65, "signature"));
+
+        // Report trailing bytes
+        let mut extended = encoded.to_vec();
+        extended.push(0);
+        let diagnostic = Transaction::decode_with_diagnostics(&extended).unwrap_err();
+        assert_eq!((diagnostic.offset, diagnostic.field), (encoded.len(), "end"));
+        assert!(matches!(diagnostic.error, CodecError::ExtraData(1)));
+    }
+
+    #[test]
+    fn test_transaction_list_cfg() {
+        let signer = PrivateKey::from_seed(0);
+        let txs = (0..MAX_BLOCK_TRANSACTIONS as u64).map(|nonce| noop(&signer, nonce)).collect::<Vec<_>>();
+
+        // Accept a list at the bound
+        let encoded = txs.encode();
+        let cfg = transaction_list_cfg(MAX_BLOCK_TRANSACTIONS);
+        assert_eq!(Vec::<Transaction>::decode_cfg(encoded.clone(), &cfg).unwrap(), txs);
+
+        // Reject a list past the bound
+        let cfg = transaction_list_cfg(MAX_BLOCK_TRANSACTIONS - 1);
+        assert!(matches!(
+            Vec::<Transaction>::decode_cfg(encoded, &cfg),
+            Err(CodecError::InvalidLength(len)) if len == MAX_BLOCK_TRANSACTIONS
+        ));
+    }
+
+    #[test]
+    fn test_block_builder_coalesce() {
+        let alice = PrivateKey::from_seed(0);
+        let bob = PrivateKey::from_seed(1);
+
+        // Transactions served interleaved by sender (as the mempool does)
+        let served = vec![noop(&alice, 0), noop(&bob, 0), noop(&alice, 1), noop(&bob, 1), noop(&alice, 2)];
+        let build = |coalesce_senders| {
+            let mut builder = BlockBuilder::new([0; 32].into(), 1, coalesce_senders);
+            for tx in served.clone() {
+                builder.push(tx).unwrap();
+            }
+            builder.build()
+        };
+
+        // Group the transactions of each sender
+        let block = build(true);
+        let nonces = block.transactions.iter()
+            .map(|tx| (tx.public_key == alice.public_key(), tx.nonce))
+            .collect::<Vec<_>>();
+        assert_eq!(nonces, vec![(true, 0), (true, 1), (true, 2), (false, 0), (false, 1)]);
+        assert!(block.verify());
+
+        // Keep the served order otherwise
+        assert_eq!(build(false).transactions, served);
+
+        // Respect the transaction limit
+        let mut builder = BlockBuilder::new([0; 32].into(), 1, true);
+        for nonce in 0..MAX_BLOCK_TRANSACTIONS as u64 {
+            builder.push(noop(&alice, nonce)).unwrap();
+        }
+        assert!(builder.is_full());
+        let extra = noop(&bob, 0);
+        assert_eq!(builder.push(extra.clone()), Err(extra));
+        assert_eq!(builder.build().transactions.len(), MAX_BLOCK_TRANSACTIONS);
+    }
+
+    #[test]
+    fn test_try_from() {
+        let signer = PrivateKey::from_seed(0);
+        let tx = noop(&signer, 0);
+        let encoded = tx.encode();
+        assert_eq!(Transaction::try_from(&encoded[..]).unwrap(), tx);
+        assert!(Transaction::try_from(&encoded[..encoded.len() - 1]).is_err());
+
+        let block = Block::new([0; 32].into(), 1, vec![tx, noop(&signer, 1)]);
+        let encoded = block.encode();
+        assert_eq!(Block::try_from(&encoded[..]).unwrap(), block);
+        assert!(Block::try_from(&encoded[..encoded.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_commit_metadata_ordering() {
+        let metadata = |height, start, head: u8| CommitMetadata { height, start, head: [head; 32].into() };
+        let mut commits = vec![metadata(2, 5, 1), metadata(1, 9, 3), metadata(2, 3, 2), metadata(1, 9, 1)];
+        commits.sort();
+        assert_eq!(commits, vec![metadata(1, 9, 1), metadata(1, 9, 3), metadata(2, 3, 2), metadata(2, 5, 1)]);
+
+        // Order by height, then by log position
+        assert!(metadata(2, 0, 0).is_ahead_of(&metadata(1, 9, 0)));
+        assert!(metadata(2, 5, 0).is_ahead_of(&metadata(2, 3, 0)));
+        assert!(!metadata(1, 9, 0).is_ahead_of(&metadata(2, 0, 0)));
+
+        // The head doesn't count as progress
+        assert!(!metadata(1, 9, 3).is_ahead_of(&metadata(1, 9, 1)));
+        assert!(metadata(1, 9, 3) > metadata(1, 9, 1));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,40 @@
+//! Round trips of the core types with the crate built without std.
+//!
+//! Run with `cargo test-no-std` (an alias for `cargo test -p fcn-swarm --no-default-features
+//! --test no_std`). The default build skips them.
+
+#[cfg(not(feature = "std"))]
+mod no_std {
+    use commonware_codec::{DecodeExt, Encode};
+    use commonware_cryptography::ed25519::PrivateKey;
+
+    use fcn_swarm::types::{Account, Block, Instruction, Transaction, BASE_GAS};
+
+    fn signer() -> PrivateKey {
+        PrivateKey::decode(&[7u8; 32][..]).unwrap()
+    }
+
+    #[test]
+    fn test_transaction_codec() {
+        let tx = Transaction::sign(&signer(), 1, 0, BASE_GAS, 0, Instruction::Noop);
+        assert!(tx.verify_signature());
+        assert_eq!(Transaction::decode(tx.encode()).unwrap(), tx);
+    }
+
+    #[test]
+    fn test_block_codec() {
+        let txs = (0..3)
+            .map(|nonce| Transaction::sign(&signer(), 1, nonce, BASE_GAS, 0, Instruction::Noop))
+            .collect();
+        let block = Block::new([0; 32].into(), 1, txs);
+        let decoded = Block::decode(block.encode()).unwrap();
+        assert!(decoded.verify());
+        assert_eq!(decoded, block);
+    }
+
+    #[test]
+    fn test_account_codec() {
+        let account = Account { nonce: 3, bread: 10 };
+        assert_eq!(Account::decode(account.encode()).unwrap(), account);
+    }
+}
\ No newline at end of file